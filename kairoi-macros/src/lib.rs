@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use syn::{parse_macro_input, Attribute, ItemFn};
 
 #[proc_macro_attribute]
 pub fn instrument(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -29,3 +29,97 @@ pub fn instrument(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
     TokenStream::from(output)
 }
+
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    if !attr.is_empty() {
+        let msg = "main macro doesn't take any arguments; use KAIROI_* environment variables instead";
+        return syn::Error::new(proc_macro2::Span::call_site(), msg)
+            .to_compile_error()
+            .into();
+    }
+
+    let attrs = &input_fn.attrs;
+    let vis = &input_fn.vis;
+    let sig = &input_fn.sig;
+    let block = &input_fn.block;
+
+    // other attributes such as `#[tokio::main]` are kept as-is,
+    // so the guard lives inside whatever runtime they build
+    let output = quote! {
+            #(#attrs)*
+            #vis #sig {
+                let __kairoi_guard = kairoi::__main();
+                #block
+            }
+        };
+
+    TokenStream::from(output)
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == name)
+    })
+}
+
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    if !attr.is_empty() {
+        let msg = "test macro doesn't take any arguments";
+        return syn::Error::new(proc_macro2::Span::call_site(), msg)
+            .to_compile_error()
+            .into();
+    }
+
+    let attrs = &input_fn.attrs;
+    let vis = &input_fn.vis;
+    let sig = &input_fn.sig;
+    let block = &input_fn.block;
+    let output = &sig.output;
+
+    // `#[tokio::test]` and friends already make it a test
+    let test_attr = if has_attr(attrs, "test") {
+        quote! {}
+    } else {
+        // a glob import of `kairoi::testing` would shadow the builtin `#[test]` with this macro
+        quote! { #[::core::prelude::v1::test] }
+    };
+    let should_panic = has_attr(attrs, "should_panic");
+
+    let (inner, call) = if sig.asyncness.is_some() {
+        (
+            quote! { async fn __kairoi_test() #output #block },
+            quote! { __kairoi_test().await },
+        )
+    } else {
+        (
+            quote! { fn __kairoi_test() #output #block },
+            quote! { __kairoi_test() },
+        )
+    };
+
+    let output = quote! {
+            #test_attr
+            #(#attrs)*
+            #vis #sig {
+                let __kairoi_capture = kairoi::testing::capture();
+                let __kairoi_report = kairoi::testing::__Report::new(&__kairoi_capture, #should_panic);
+
+                #inner
+                let __kairoi_result = #call;
+
+                __kairoi_report.finish(&__kairoi_result);
+                __kairoi_result
+            }
+        };
+
+    TokenStream::from(output)
+}
@@ -0,0 +1,89 @@
+use crossbeam_channel::{unbounded, Sender};
+use kairoi::{Event, GlobalHandler, Handler, Level, SendSyncError, SpanRef};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+struct LatencyHandler {
+    tx: Sender<Instant>,
+}
+
+impl Handler for LatencyHandler {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        if let Event::Log(_) = event {
+            self.tx.send(Instant::now())?;
+        }
+        Ok(())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+}
+
+fn cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+fn idle_cpu() {
+    const IDLE: Duration = Duration::from_secs(3);
+
+    let handler = GlobalHandler::builder()
+        .handler(Box::new(LatencyHandler { tx: unbounded().0 }))
+        .build().unwrap();
+
+    sleep(Duration::from_millis(100));
+    let begin = cpu_time();
+    sleep(IDLE);
+    let used = cpu_time() - begin;
+
+    drop(handler);
+
+    println!(
+        "idle cpu:    {:>10.3?} over {IDLE:?} ({:.3}%)",
+        used,
+        used.as_secs_f64() / IDLE.as_secs_f64() * 100.0
+    );
+}
+
+fn log_latency() {
+    const SAMPLES: usize = 500;
+
+    let (tx, rx) = unbounded();
+    let handler = GlobalHandler::builder()
+        .handler(Box::new(LatencyHandler { tx }))
+        .build().unwrap();
+
+    let mut samples = Vec::with_capacity(SAMPLES);
+    for _ in 0..SAMPLES {
+        let begin = Instant::now();
+        Event::log(Level::Info, "latency".to_string()).submit();
+        let handled = rx.recv().unwrap();
+        samples.push(handled - begin);
+
+        // sparse logging; leave the loop idle between events
+        sleep(Duration::from_millis(2));
+    }
+
+    drop(handler);
+
+    samples.sort();
+    let mean = samples.iter().sum::<Duration>() / SAMPLES as u32;
+    println!(
+        "log latency: mean {:>10.3?}, p50 {:>10.3?}, p99 {:>10.3?}",
+        mean,
+        samples[SAMPLES / 2],
+        samples[SAMPLES * 99 / 100]
+    );
+}
+
+fn main() {
+    idle_cpu();
+    log_latency();
+}
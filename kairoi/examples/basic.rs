@@ -2,7 +2,7 @@ use kairoi::{info, AddConsoleHandler, GlobalHandler};
 
 #[tokio::main]
 async fn main() {
-    let global_handler = GlobalHandler::builder().console_handler().build();
+    let global_handler = GlobalHandler::builder().console_handler().build().unwrap();
 
     info!("Hello");
 
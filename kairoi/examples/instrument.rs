@@ -21,7 +21,7 @@ async fn some_instrument() {
 
 #[tokio::main]
 async fn main() {
-    let global_handler = GlobalHandler::builder().console_handler().build();
+    let global_handler = GlobalHandler::builder().console_handler().build().unwrap();
 
     info!("Hello");
     some_instrument().await;
@@ -0,0 +1,40 @@
+use kairoi::{
+    info, instrument, AddConsoleHandler, AddIsolatedHandler, Event, GlobalHandler, Handler,
+    Overflow, SendSyncError, SpanRef,
+};
+use std::time::Duration;
+use tokio::time::sleep;
+
+// stands for a network exporter or an fsync'ing file sink
+struct SlowHandler;
+
+impl Handler for SlowHandler {
+    fn handle(&self, _event: &Event) -> Result<(), SendSyncError> {
+        std::thread::sleep(Duration::from_millis(200));
+        Ok(())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+}
+
+#[instrument]
+async fn work() {
+    for i in 1..=20 {
+        info!("step {}/20", i);
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let global_handler = GlobalHandler::builder()
+        .console_handler()
+        .isolated_handler(Box::new(SlowHandler), 8, Overflow::DropOldest)
+        .build().unwrap();
+
+    work().await;
+
+    global_handler.shutdown(Duration::from_secs(5)).unwrap();
+}
@@ -0,0 +1,19 @@
+use kairoi::{info, instrument, warn};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[instrument]
+async fn work() {
+    for i in 1..=5 {
+        info!("step {}/5", i);
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[kairoi::main]
+#[tokio::main]
+async fn main() {
+    info!("Hello");
+    work().await;
+    warn!("no need to drop a handler by hand");
+}
@@ -0,0 +1,29 @@
+use kairoi::{info, instrument};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[instrument]
+async fn parse(fail: bool) {
+    sleep(Duration::from_millis(300)).await;
+    if fail {
+        panic!("unexpected token");
+    }
+}
+
+#[instrument]
+async fn fetch(fail: bool) {
+    info!("fetching");
+    parse(fail).await;
+}
+
+#[kairoi::main]
+#[tokio::main]
+async fn main() {
+    let ok = tokio::spawn(fetch(false));
+    let failing = tokio::spawn(fetch(true));
+
+    let _ = failing.await;
+    let _ = ok.await;
+
+    info!("still logging after the panic");
+}
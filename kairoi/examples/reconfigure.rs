@@ -0,0 +1,50 @@
+use kairoi::{
+    debug, info, AddConsoleHandler, Event, GlobalHandler, Handler, Level, SendSyncError, SpanRef,
+};
+use std::time::Duration;
+use tokio::time::sleep;
+
+// stands for a debug file sink that is too noisy to keep on all the time
+struct DebugSink;
+
+impl Handler for DebugSink {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        if let Event::Log(log) = event {
+            eprintln!("[debug-sink] {:?}: {}", log.level(), log.message());
+        }
+        Ok(())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let global_handler = GlobalHandler::builder().console_handler().build().unwrap();
+
+    // e.g. move this into a SIGUSR1 handler
+    let control = global_handler.control();
+    let console = global_handler.handler_ids()[0];
+    tokio::spawn(async move {
+        sleep(Duration::from_millis(500)).await;
+        let id = control.attach(Box::new(DebugSink)).unwrap();
+
+        sleep(Duration::from_millis(500)).await;
+        control.set_level(id, Some(Level::Info)).unwrap();
+        control.set_fps(30).unwrap();
+        control.set_level(console, Some(Level::Info)).unwrap();
+
+        sleep(Duration::from_millis(500)).await;
+        control.detach(id).unwrap();
+    });
+
+    for i in 0..20 {
+        info!("tick {}", i);
+        debug!("details of tick {}", i);
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    drop(global_handler);
+}
@@ -0,0 +1,29 @@
+use kairoi::{info, instrument};
+use std::io::{stdin, stdout, Write};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[instrument]
+async fn greet() {
+    info!("asking for a name");
+    sleep(Duration::from_millis(300)).await;
+
+    // the live tree would otherwise be drawn over the prompt
+    let name = kairoi::suspend(|| {
+        print!("name: ");
+        stdout().flush().unwrap();
+
+        let mut name = String::new();
+        stdin().read_line(&mut name).unwrap();
+        name.trim().to_string()
+    });
+
+    kairoi::suspended_println!("hello, {name}");
+    sleep(Duration::from_millis(300)).await;
+}
+
+#[kairoi::main]
+#[tokio::main]
+async fn main() {
+    greet().await;
+}
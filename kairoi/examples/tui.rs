@@ -0,0 +1,26 @@
+use kairoi::{info, instrument, AddTuiHandler, GlobalHandler, Progress, Span};
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[instrument]
+async fn download(name: &str) {
+    let span = Span::current();
+    let data = Span::default().with_name(name.to_string());
+
+    for i in 0..=20 {
+        span.update(data.with_progress(Progress::new(20, i)));
+        if i % 5 == 0 {
+            info!("{name}: {i}/20 chunks");
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let global_handler = GlobalHandler::builder().tui_handler().build().unwrap();
+
+    tokio::join!(download("a.tar.gz"), download("b.tar.gz"), download("c.tar.gz"));
+
+    drop(global_handler);
+}
@@ -0,0 +1,145 @@
+use crate::handler::{AggregatedError, Command};
+use crate::policy::Slot;
+use crate::{Dispatcher, Event, FailurePolicy, Handler, HandlerId, Level};
+use crossbeam_channel::{bounded, Sender};
+use std::cell::Cell;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type Filter = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
+
+const SUSPEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+thread_local! {
+    static SUSPENDED: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn is_suspended() -> bool {
+    SUSPENDED.with(Cell::get)
+}
+
+struct SuspendGuard;
+
+impl Drop for SuspendGuard {
+    fn drop(&mut self) {
+        SUSPENDED.with(|v| v.set(false));
+    }
+}
+
+/// Erases the live output of the current dispatcher's handlers and keeps them from drawing
+/// while `f` runs, so `f` can write to the terminal, run a child process or prompt for input.
+///
+/// Logs submitted meanwhile are shown once `f` returns.
+/// Nested calls and calls without a running `GlobalHandler` just run `f`.
+///
+/// The logging thread waits for `f` to return, so `flush`, `shutdown` and other
+/// `HandlerControl` calls made from other threads meanwhile wait as well, and logging
+/// blocks once the event queue is full.
+pub fn suspend<T, F: FnOnce() -> T>(f: F) -> T {
+    match Dispatcher::current().control() {
+        Some(control) => control.suspend(SUSPEND_TIMEOUT, f),
+        None => f(),
+    }
+}
+
+/// Cloneable handle to reconfigure a running `GlobalHandler` from any thread.
+#[derive(Clone)]
+pub struct HandlerControl {
+    control: Sender<Command>,
+}
+
+impl HandlerControl {
+    pub(crate) fn new(control: Sender<Command>) -> Self {
+        Self { control }
+    }
+
+    fn not_running() -> AggregatedError {
+        AggregatedError::single("Logging thread is not running")
+    }
+
+    fn send(&self, command: Command) -> Result<(), AggregatedError> {
+        self.control.send(command).map_err(|_| Self::not_running())
+    }
+
+    fn request<T>(
+        &self,
+        make: impl FnOnce(Sender<T>) -> Command,
+    ) -> Result<T, AggregatedError> {
+        let (ack, ack_rx) = bounded(1);
+        self.send(make(ack))?;
+        ack_rx.recv().map_err(|_| Self::not_running())
+    }
+
+    pub fn attach(&self, handler: Box<dyn Handler>) -> Result<HandlerId, AggregatedError> {
+        self.attach_with_policy(handler, FailurePolicy::Remove)
+    }
+
+    pub fn attach_with_policy(
+        &self,
+        handler: Box<dyn Handler>,
+        policy: FailurePolicy,
+    ) -> Result<HandlerId, AggregatedError> {
+        let slot = Slot::new(handler, policy);
+        let id = slot.id();
+        self.send(Command::Attach(slot))?;
+        Ok(id)
+    }
+
+    /// Flushes and shuts down the handler before removing it.
+    pub fn detach(&self, id: HandlerId) -> Result<(), AggregatedError> {
+        self.request(|ack| Command::Detach(id, ack))?
+    }
+
+    /// Ids of the attached handlers, in registration order.
+    pub fn handlers(&self) -> Result<Vec<HandlerId>, AggregatedError> {
+        self.request(Command::Handlers)
+    }
+
+    /// Delivers logs up to `level` (inclusive) to the handler; `None` lets everything through.
+    pub fn set_level(&self, id: HandlerId, level: Option<Level>) -> Result<(), AggregatedError> {
+        self.request(|ack| Command::SetLevel(id, level, ack))?
+    }
+
+    /// Delivers only events accepted by `filter` to the handler; `None` removes the filter.
+    pub fn set_filter(&self, id: HandlerId, filter: Option<Filter>) -> Result<(), AggregatedError> {
+        self.request(|ack| Command::SetFilter(id, filter, ack))?
+    }
+
+    pub fn set_fps(&self, fps: u32) -> Result<(), AggregatedError> {
+        self.send(Command::SetFps(fps.max(1)))
+    }
+
+    /// Blocks until every event submitted before this call is handled,
+    /// a frame is drawn and all handlers are flushed.
+    pub fn flush(&self) -> Result<(), AggregatedError> {
+        self.request(Command::Flush)?
+    }
+
+    pub(crate) fn flush_within(&self, timeout: Duration) -> Result<(), AggregatedError> {
+        let (ack, ack_rx) = bounded(1);
+        self.send(Command::Flush(ack))?;
+        ack_rx
+            .recv_timeout(timeout)
+            .map_err(|_| AggregatedError::single("Logging thread didn't flush in time"))?
+    }
+
+    /// Flushes, erases live output of all handlers and keeps them from drawing while `f` runs.
+    pub(crate) fn suspend<T, F: FnOnce() -> T>(&self, timeout: Duration, f: F) -> T {
+        if is_suspended() {
+            return f();
+        }
+        SUSPENDED.with(|v| v.set(true));
+        let _guard = SuspendGuard;
+
+        let (ack, ack_rx) = bounded(1);
+        let (resume, resume_rx) = bounded::<()>(0);
+
+        if self.send(Command::Suspend(ack, resume_rx)).is_ok() {
+            let _ = ack_rx.recv_timeout(timeout);
+        }
+
+        let v = f();
+        drop(resume);
+        v
+    }
+}
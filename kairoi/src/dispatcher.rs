@@ -0,0 +1,161 @@
+use crate::channel::channel;
+use crate::handler::is_handler_thread;
+use crate::{Event, HandlerControl, Node, Span, SpanRef};
+use crossbeam_channel::{Receiver, SendError, Sender};
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::task_local;
+
+struct Inner {
+    tx: Sender<Event>,
+    rx: Receiver<Event>,
+    root: SpanRef,
+    driven: AtomicBool,
+    control: Mutex<Option<HandlerControl>>,
+}
+
+/// Owns an event channel and a span tree.
+///
+/// Events and spans go to the dispatcher of the current task, then the one set for the
+/// current thread, then the process default.
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+lazy_static! {
+    static ref GLOBAL: RwLock<Dispatcher> = RwLock::new(Dispatcher::new());
+}
+
+task_local! {
+    static TASK: Dispatcher;
+}
+
+thread_local! {
+    static THREAD: RefCell<Option<Dispatcher>> = const { RefCell::new(None) };
+}
+
+impl Default for Dispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            inner: Arc::new(Inner {
+                tx,
+                rx,
+                root: Arc::new(Node::new(Span::default())),
+                driven: AtomicBool::new(false),
+                control: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// The process default.
+    pub fn global() -> Self {
+        GLOBAL.read().clone()
+    }
+
+    /// Replaces the process default; events already queued stay with the previous one.
+    pub fn set_global(dispatcher: Dispatcher) {
+        *GLOBAL.write() = dispatcher;
+    }
+
+    pub(crate) fn with_current<T, F: FnOnce(&Dispatcher) -> T>(f: F) -> T {
+        let mut f = Some(f);
+        if let Ok(v) = TASK.try_with(|d| f.take().unwrap()(d)) {
+            return v;
+        }
+
+        // don't hold the lock of the process default while submitting; the channel may be full
+        let d = THREAD
+            .try_with(|d| d.borrow().clone())
+            .ok()
+            .flatten()
+            .unwrap_or_else(Self::global);
+        f.take().unwrap()(&d)
+    }
+
+    pub fn current() -> Self {
+        Self::with_current(Dispatcher::clone)
+    }
+
+    /// Makes this the dispatcher of the current thread until the guard is dropped.
+    pub fn set_default(&self) -> DefaultGuard {
+        let prev = THREAD.with(|d| d.borrow_mut().replace(self.clone()));
+        DefaultGuard { prev }
+    }
+
+    /// Runs `f` with this as the dispatcher of the current thread.
+    pub fn with<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        let _guard = self.set_default();
+        Span::detached(f)
+    }
+
+    /// Runs `f` with this as the dispatcher of the current task.
+    pub async fn scope<F: Future>(&self, f: F) -> F::Output {
+        TASK.scope(self.clone(), Span::detached_async(f)).await
+    }
+
+    pub fn root(&self) -> SpanRef {
+        self.inner.root.clone()
+    }
+
+    pub fn submit(&self, event: Event) {
+        let mut i = 0;
+        let mut v = event;
+        while let Err(SendError(e)) = self.inner.tx.send(v) {
+            v = e;
+            i += 1;
+            if i == 5 {
+                eprintln!("[kairoi] failed to submit event to event queue");
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn rx(&self) -> &Receiver<Event> {
+        &self.inner.rx
+    }
+
+    /// Marks this dispatcher as driven by a handler thread; fails if it already is.
+    pub(crate) fn claim(&self) -> bool {
+        !self.inner.driven.swap(true, Ordering::AcqRel)
+    }
+
+    pub(crate) fn release(&self) {
+        self.inner.driven.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn set_control(&self, control: Option<HandlerControl>) {
+        *self.inner.control.lock() = control;
+    }
+
+    /// Control of the handler thread driving this dispatcher,
+    /// unless called from a thread that thread may be waiting for.
+    pub(crate) fn control(&self) -> Option<HandlerControl> {
+        if is_handler_thread() {
+            return None;
+        }
+        self.inner.control.lock().clone()
+    }
+}
+
+pub struct DefaultGuard {
+    prev: Option<Dispatcher>,
+}
+
+impl Drop for DefaultGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        THREAD.with(|d| *d.borrow_mut() = prev);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{install_panic_hook, AddConsoleHandler, GlobalHandler};
+use std::env;
+
+fn console_enabled() -> bool {
+    match env::var("KAIROI_CONSOLE") {
+        Ok(v) => !matches!(v.as_str(), "0" | "false" | "off"),
+        Err(_) => true,
+    }
+}
+
+#[doc(hidden)]
+pub fn __main() -> GlobalHandler {
+    install_panic_hook();
+
+    let mut builder = GlobalHandler::builder().from_env();
+    if console_enabled() {
+        builder = builder.console_handler();
+    }
+
+    builder
+        .build()
+        .unwrap_or_else(|e| panic!("[kairoi] failed to start logging: {e:?}"))
+}
@@ -1,10 +1,13 @@
+use std::fmt::{Display, Formatter};
 use std::sync::Arc;
-use crate::channel::tx;
-use crate::{Span, SpanRef};
-use crossbeam_channel::SendError;
+use crate::{Dispatcher, Span, SpanRef};
+#[cfg(feature = "serde")]
+use crate::{schema, Id, Node, Progress};
 use std::time::SystemTime;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Level {
     Error,
     Warn,
@@ -13,20 +16,45 @@ pub enum Level {
     Trace,
 }
 
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
 pub struct Log {
     timestamp: SystemTime,
     level: Level,
     message: String,
+    fields: Vec<(String, String)>,
     span: SpanRef,
+    // nodes hold their parent weakly, so a deserialized span needs its detached tree kept alive
+    #[cfg(feature = "serde")]
+    _root: Option<SpanRef>,
 }
 
 impl Log {
-    fn new(level: Level, message: String) -> Self {
+    fn new(level: Level, message: String, fields: Vec<(String, String)>) -> Self {
         Self {
             timestamp: SystemTime::now(),
             level,
             message,
+            fields,
             span: Span::current(),
+            #[cfg(feature = "serde")]
+            _root: None,
         }
     }
 
@@ -42,11 +70,168 @@ impl Log {
         &self.message
     }
 
+    /// Key-value pairs given along with the message, in order.
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
     pub fn span(&self) -> &SpanRef {
         &self.span
     }
 }
 
+// the keys of `JsonFormatter`, and the spans from the outermost one down to the one a log
+// was written in; the keys derived from those are left to them when reading it back
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LogRecord {
+    #[serde(with = "crate::schema::rfc3339")]
+    timestamp: SystemTime,
+    #[serde(skip_deserializing)]
+    kind: &'static str,
+    level: Level,
+    message: String,
+    #[serde(with = "fields")]
+    fields: Vec<(String, String)>,
+    #[serde(skip_deserializing)]
+    span_id: Option<Id>,
+    #[serde(skip_deserializing)]
+    parent_id: Option<Id>,
+    #[serde(skip_deserializing)]
+    span_path: Vec<String>,
+    #[serde(skip_deserializing)]
+    progress: Option<Progress>,
+    spans: Vec<SpanRecord>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SpanRecord {
+    span_id: Id,
+    #[serde(flatten)]
+    span: Span,
+}
+
+// an object, keeping the order fields were given in
+#[cfg(feature = "serde")]
+mod fields {
+    use serde::de::{MapAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt::Formatter;
+
+    pub fn serialize<S: Serializer>(fields: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(fields.iter().map(|(k, v)| (k, v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, String)>, D::Error> {
+        struct Fields;
+
+        impl<'de> Visitor<'de> for Fields {
+            type Value = Vec<(String, String)>;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("an object of strings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(fields)
+            }
+        }
+
+        deserializer.deserialize_map(Fields)
+    }
+}
+
+/// Written as the object `JsonFormatter` writes, with the span of the log and its
+/// ancestors under `spans`; a deserialized log gets a tree of its own, detached from
+/// any dispatcher, with the ids it was written with.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Log {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut spans = Vec::new();
+        let mut node = Some(self.span.clone());
+        while let Some(span) = node.filter(|span| span.depth() > 0) {
+            spans.push(SpanRecord {
+                span_id: span.id(),
+                span: span.value().as_ref().clone(),
+            });
+            node = span.parent();
+        }
+        spans.reverse();
+
+        LogRecord {
+            timestamp: self.timestamp,
+            kind: "log",
+            level: self.level,
+            message: self.message.clone(),
+            fields: self.fields.clone(),
+            span_id: schema::span_id(&self.span),
+            parent_id: schema::parent_id(&self.span),
+            span_path: self.span.path(),
+            progress: schema::span_id(&self.span).and(self.span.value().progress()),
+            spans,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Log {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = LogRecord::deserialize(deserializer)?;
+
+        let root = Arc::new(Node::new(Span::default()));
+        let mut span = root.clone();
+        for SpanRecord { span_id, span: value } in record.spans {
+            span = span.add(Node::with_id(span_id, value));
+        }
+
+        Ok(Self {
+            timestamp: record.timestamp,
+            level: record.level,
+            message: record.message,
+            fields: record.fields,
+            span,
+            _root: Some(root),
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EventKind {
+    Log,
+    SpanBegin,
+    SpanEnd,
+}
+
+/// What a handler gets to see of an event before deciding whether to handle it.
+#[derive(Copy, Clone)]
+pub struct Metadata<'a> {
+    kind: EventKind,
+    level: Option<Level>,
+    span: &'a SpanRef,
+}
+
+impl<'a> Metadata<'a> {
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Level of a log; `None` for span events.
+    pub fn level(&self) -> Option<Level> {
+        self.level
+    }
+
+    pub fn span(&self) -> &'a SpanRef {
+        self.span
+    }
+}
+
+#[derive(Clone)]
 pub enum Event {
     Log(Arc<Log>),
     SpanBegin(SpanRef),
@@ -55,7 +240,11 @@ pub enum Event {
 
 impl Event {
     pub fn log(level: Level, message: String) -> Self {
-        Event::Log(Arc::new(Log::new(level, message)))
+        Self::log_with_fields(level, message, Vec::new())
+    }
+
+    pub fn log_with_fields(level: Level, message: String, fields: Vec<(String, String)>) -> Self {
+        Event::Log(Arc::new(Log::new(level, message, fields)))
     }
 
     pub fn span_begin(span: SpanRef) -> Self {
@@ -66,16 +255,27 @@ impl Event {
         Self::SpanEnd(span)
     }
 
-    pub fn submit(self) {
-        let mut i = 0;
-        let mut v = self;
-        while let Err(SendError(e)) = tx().send(v) {
-            v = e;
-            i += 1;
-            if i == 5 {
-                eprintln!("[kairoi] failed to submit event to event queue");
-                break;
-            }
+    pub fn metadata(&self) -> Metadata<'_> {
+        match self {
+            Event::Log(log) => Metadata {
+                kind: EventKind::Log,
+                level: Some(log.level()),
+                span: log.span(),
+            },
+            Event::SpanBegin(span) => Metadata {
+                kind: EventKind::SpanBegin,
+                level: None,
+                span,
+            },
+            Event::SpanEnd(span) => Metadata {
+                kind: EventKind::SpanEnd,
+                level: None,
+                span,
+            },
         }
     }
+
+    pub fn submit(self) {
+        Dispatcher::with_current(|d| d.submit(self));
+    }
 }
@@ -1,7 +1,9 @@
 use crate::error::SendSyncError;
-use crate::{Level, Log, SpanRef};
-use chrono::{DateTime, Local};
+use crate::{Color, Log, SpanRef, Status, Theme, Timestamp};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
 use std::fmt::Arguments;
+use std::time::{Duration, SystemTime};
 
 pub trait Write {
     fn write_fmt(&mut self, fmt: Arguments) -> Result<(), SendSyncError>;
@@ -27,80 +29,240 @@ pub trait Formatter<T>: Send + Sync {
     fn format(&self, to: &mut Writer, value: &T) -> Result<(), SendSyncError>;
 }
 
-pub trait FormatterSet: Formatter<Log> + Formatter<SpanRef> {}
+pub trait FormatterSet: Formatter<Log> + Formatter<SpanRef> {
+    /// Line printed once `span` has ended, like `finished codegen in 3.2s`.
+    fn format_completion(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        let value = span.value();
+        let t = format_duration(value.elapsed());
+        let name = value.name();
 
-pub struct DefaultFormatter;
+        match value.status() {
+            Status::Running | Status::Completed => writeln!(to, "finished {name} in {t}"),
+            Status::Failed => writeln!(to, "{name} failed after {t}"),
+            Status::Panicked => writeln!(to, "{name} panicked after {t}"),
+            Status::Cancelled => writeln!(to, "{name} was cancelled after {t}"),
+        }
+    }
+
+    /// `span` as a row of a span tree drawn by a handler, which may differ from the tree of
+    /// spans when some are collapsed, sorted or have ended; `last` is as for `tree_indent`,
+    /// down to the span itself.
+    fn format_tree_span(&self, to: &mut Writer, span: &SpanRef, last: &[bool]) -> Result<(), SendSyncError> {
+        let _ = last;
+        Formatter::<SpanRef>::format(self, to, span)
+    }
+
+    /// Drawn before a row of a span tree that isn't a span itself, like `… 3 more running`
+    /// or a log shown beneath its span.
+    ///
+    /// `last` tells, for each level of nesting below the top, whether the row there is the
+    /// last one drawn below its parent; `branch` is set for rows that hang off the tree, and
+    /// unset for lines continuing the row above.
+    fn tree_indent(&self, last: &[bool], branch: bool) -> String {
+        let _ = branch;
+        "  ".repeat(last.len())
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+
+// chrono panics on invalid formats while writing
+pub(crate) fn check_time_format(format: &str) -> Result<(), SendSyncError> {
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(format!("invalid time format `{format}`").into());
+    }
+    Ok(())
+}
+
+pub(crate) fn format_duration(d: Duration) -> String {
+    let mut t = d.as_secs_f32();
+    let mut suffix = 's';
+    if t > 60_f32 {
+        t /= 60_f32;
+        suffix = 'm';
+    }
+    if t > 60_f32 {
+        t /= 60_f32;
+        suffix = 'h';
+    }
+
+    format!("{t:.1}{suffix}")
+}
+
+pub struct DefaultFormatter {
+    color: bool,
+    theme: Theme,
+    // for relative timestamps
+    created: SystemTime,
+}
+
+impl Default for DefaultFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl DefaultFormatter {
     pub fn new() -> Self {
-        Self {}
+        Self::with_color(true)
     }
 
-    fn indent(to: &mut Writer, depth: usize) -> Result<(), SendSyncError> {
-        for _ in 0..depth {
-            write!(to, "  ")?;
+    /// Without color, no escape sequence is written at all.
+    pub fn with_color(color: bool) -> Self {
+        Self {
+            color,
+            theme: Theme::default(),
+            created: SystemTime::now(),
         }
+    }
 
-        Ok(())
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    fn style(&self, code: &'static str) -> &'static str {
+        if self.color { code } else { "" }
+    }
+
+    fn color(&self, color: Color) -> String {
+        if self.color { color.escape() } else { String::new() }
+    }
+
+    fn timestamp(&self, t: SystemTime) -> Option<String> {
+        Some(match self.theme.timestamp_format() {
+            Timestamp::Local(format) => DateTime::<Local>::from(t).format(format).to_string(),
+            Timestamp::Utc(format) => DateTime::<Utc>::from(t).format(format).to_string(),
+            Timestamp::Relative => {
+                let t = t.duration_since(self.created).unwrap_or_default();
+                format!("+{:.3}s", t.as_secs_f64())
+            }
+            Timestamp::None => return None,
+        })
+    }
+
+    fn glyphs(&self, last: &[bool], branch: bool) -> String {
+        let glyphs = self.theme.tree_glyphs();
+        let mut indent = String::new();
+        for (i, &is_last) in last.iter().enumerate() {
+            let glyph = match (branch && i + 1 == last.len(), is_last) {
+                (true, false) => &glyphs.branch,
+                (true, true) => &glyphs.last,
+                (false, false) => &glyphs.vertical,
+                (false, true) => &glyphs.space,
+            };
+            indent.push_str(glyph);
+        }
+        indent
+    }
+
+    // glyphs of the ancestors below the top level, then of the span itself
+    fn indent(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        let mut nodes = vec![span.clone()];
+        while let Some(parent) = nodes[nodes.len() - 1].parent().filter(|p| p.depth() > 0) {
+            nodes.push(parent);
+        }
+
+        // spans that have ended are no longer among the children, and count as last
+        let last: Vec<bool> = nodes
+            .iter()
+            .rev()
+            .skip(1)
+            .map(|node| {
+                node.parent().is_none_or(|parent| {
+                    let mut rest = parent.children().skip_while(|child| child.id() != node.id());
+                    rest.next().is_none() || rest.next().is_none()
+                })
+            })
+            .collect();
+
+        write!(to, "{}", self.glyphs(&last, true))
     }
 
     pub fn format_log(to: &mut Writer, log: &Log) -> Result<(), SendSyncError> {
-        let t: DateTime<Local> = log.timestamp().into();
-        let t = t.format("%+");
-
-        // use of 3-bit color sequence
-        let level = match log.level() {
-            Level::Error => "\x1b[31mERROR", // red
-            Level::Warn => "\x1b[33m WARN",  // yellow
-            Level::Info => "\x1b[32m INFO",  // green
-            Level::Debug => "\x1b[36mDEBUG", // cyan
-            Level::Trace => "\x1b[35mTRACE", // magenta
-        };
+        Self::default().write_log(to, log)
+    }
+
+    pub fn format_span(to: &mut Writer, value: &SpanRef) -> Result<(), SendSyncError> {
+        Self::default().write_span(to, value)
+    }
+
+    fn write_log(&self, to: &mut Writer, log: &Log) -> Result<(), SendSyncError> {
+        let gray = self.style("\x1b[90m");
+        let color = self.color(self.theme.level_color(log.level()));
+        let bold = self.style("\x1b[1m");
+        let reset = self.style(RESET);
 
+        if let Some(t) = self.timestamp(log.timestamp()) {
+            write!(to, "{gray}{t}{reset} ")?;
+        }
+
+        let level = self.theme.level_label(log.level());
+        let width = self.theme.label_width();
         let message = log.message();
 
         if let Some(parent) = &log.span().parent() {
             to.write_fmt(format_args!(
-                "\x1b[90m{t}\x1b[0m {level}\x1b[0m \x1b[1m{}\x1b[0m: {message}\n",
+                "{color}{level:>width$}{reset} {bold}{}{reset}: {message}",
                 parent.value().name()
             ))?;
         } else {
-            to.write_fmt(format_args!(
-                "\x1b[90m{t}\x1b[0m {level}\x1b[0m: {message}\n"
-            ))?;
+            to.write_fmt(format_args!("{color}{level:>width$}{reset}: {message}"))?;
         };
+        for (key, value) in log.fields() {
+            write!(to, " {gray}{key}={reset}{value}")?;
+        }
+        writeln!(to)?;
 
         Ok(())
     }
 
-    pub fn format_span(to: &mut Writer, value: &SpanRef) -> Result<(), SendSyncError> {
-        let depth = if value.depth() > 0 {
-            value.depth() - 1
-        } else {
-            value.depth()
-        };
-        Self::indent(to, depth)?;
+    fn write_span(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        self.indent(to, span)?;
+        self.write_span_row(to, span)
+    }
 
-        let value = value.value();
-        let elapsed = value.timestamp().elapsed().unwrap_or_default();
+    fn write_span_row(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        let value = span.value();
+        let elapsed = value.elapsed();
 
-        let ch = vec!['⠖', '⠲', '⠴', '⠦'][((elapsed.as_millis() / 100) % 4) as usize];
+        let ch = self.theme.spinner_frame(elapsed);
+        let t = format_duration(elapsed);
 
-        let mut t = elapsed.as_secs_f32();
-        let mut suffix = 's';
-        if t > 60_f32 {
-            t /= 60_f32;
-            suffix = 'm';
-        }
-        if t > 60_f32 {
-            t /= 60_f32;
-            suffix = 'h';
+        let red = self.style("\x1b[31m");
+        let green = self.style("\x1b[32m");
+        let reset = self.style(RESET);
+        match value.status() {
+            Status::Running => {}
+            Status::Completed => {
+                to.write_fmt(format_args!("{green}✔{reset} {} [{t}]\n", value.name()))?;
+                return Ok(());
+            }
+            Status::Failed => {
+                to.write_fmt(format_args!("{red}✘{reset} {} (failed)\n", value.name()))?;
+                return Ok(());
+            }
+            Status::Panicked => {
+                to.write_fmt(format_args!(
+                    "{red}✘{reset} {} [{t}] (panicked)\n",
+                    value.name()
+                ))?;
+                return Ok(());
+            }
+            Status::Cancelled => {
+                let gray = self.style("\x1b[90m");
+                to.write_fmt(format_args!(
+                    "{gray}-{reset} {} [{t}] (cancelled)\n",
+                    value.name()
+                ))?;
+                return Ok(());
+            }
         }
 
         let args = match value.progress() {
-            None => format_args!("{ch} {} [{t:.1}{suffix}]\n", value.name()),
+            None => format_args!("{ch} {} [{t}]\n", value.name()),
             Some(p) => format_args!(
-                "{ch} {} ({}/{}) [{t:.1}{suffix}]\n",
+                "{ch} {} ({}/{}) [{t}]\n",
                 value.name(),
                 p.progress(),
                 p.total()
@@ -115,14 +277,23 @@ impl DefaultFormatter {
 
 impl Formatter<Log> for DefaultFormatter {
     fn format(&self, to: &mut Writer, value: &Log) -> Result<(), SendSyncError> {
-        Self::format_log(to, value)
+        self.write_log(to, value)
     }
 }
 
 impl Formatter<SpanRef> for DefaultFormatter {
     fn format(&self, to: &mut Writer, value: &SpanRef) -> Result<(), SendSyncError> {
-        Self::format_span(to, value)
+        self.write_span(to, value)
     }
 }
 
-impl FormatterSet for DefaultFormatter {}
+impl FormatterSet for DefaultFormatter {
+    fn format_tree_span(&self, to: &mut Writer, span: &SpanRef, last: &[bool]) -> Result<(), SendSyncError> {
+        write!(to, "{}", self.glyphs(last, true))?;
+        self.write_span_row(to, span)
+    }
+
+    fn tree_indent(&self, last: &[bool], branch: bool) -> String {
+        self.glyphs(last, branch)
+    }
+}
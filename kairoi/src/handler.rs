@@ -1,24 +1,71 @@
-use crate::channel::rx;
 use crate::error::SendSyncError;
-use crate::{Event, Span, SpanRef};
-use crossbeam_channel::TryRecvError;
-use parking_lot::Mutex;
+use crate::policy::{default_error_callback, ErrorCallback, Slot, Slots};
+use crate::{
+    Dispatcher, Event, FailurePolicy, Filter, HandlerControl, HandlerFailure, HandlerId, Level,
+    Metadata, SpanRef,
+};
+use crossbeam_channel::{at, never, select_biased, unbounded, Receiver, RecvTimeoutError, Sender};
+use std::cell::Cell;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
 use std::thread;
-use std::thread::{sleep, JoinHandle};
-use std::time::{Duration, Instant};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 pub trait Handler: Send {
     fn handle(&self, event: &Event) -> Result<(), SendSyncError>;
     fn tick(&self, root: &SpanRef) -> Result<(), SendSyncError>;
+
+    /// Events this returns `false` for never reach `handle`.
+    fn interest(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    /// Called on the logging thread before the first event is handled.
+    fn on_start(&self) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    /// Called after all pending events are handled and the last frame is drawn.
+    /// Buffered sinks should write out everything they hold.
+    fn flush(&self) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    /// Called once, right after the final `flush`; no more events follow.
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    /// Erase whatever is drawn live, so foreign output can be written to the terminal.
+    /// Drawing resumes with the next `tick`.
+    fn suspend(&self) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    /// Sinks that don't render anything can opt out of `tick` entirely.
+    fn wants_tick(&self) -> bool {
+        true
+    }
+
+    /// Interval between two ticks; `None` follows the frame rate of the global handler.
+    fn tick_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether the handler still has something to redraw without new events or live spans,
+    /// like spans fading out; ticks stop otherwise.
+    fn animating(&self) -> bool {
+        false
+    }
 }
 
 pub struct GlobalHandlerBuilder {
     fps: u32,
-    handlers: Vec<Box<dyn Handler>>,
+    handlers: Vec<(Box<dyn Handler>, Option<FailurePolicy>)>,
+    failure_policy: FailurePolicy,
+    on_error: ErrorCallback,
+    dispatcher: Option<Dispatcher>,
 }
 
 impl GlobalHandlerBuilder {
@@ -26,38 +73,145 @@ impl GlobalHandlerBuilder {
         Self {
             fps: 15,
             handlers: vec![],
+            failure_policy: FailurePolicy::Remove,
+            on_error: default_error_callback(),
+            dispatcher: None,
         }
     }
 
+    /// Dispatcher to drive; the process default if not set.
+    pub fn dispatcher(mut self, dispatcher: Dispatcher) -> Self {
+        self.dispatcher = Some(dispatcher);
+        self
+    }
+
+    /// Clamped to at least 1.
     pub fn fps(mut self, fps: u32) -> Self {
-        self.fps = fps;
+        self.fps = fps.max(1);
+        self
+    }
+
+    pub fn from_env(mut self) -> Self {
+        if let Some(fps) = std::env::var("KAIROI_FPS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v > 0)
+        {
+            self.fps = fps;
+        }
         self
     }
 
     pub fn handler(mut self, handler: Box<dyn Handler>) -> Self {
-        self.handlers.push(handler);
+        self.handlers.push((handler, None));
         self
     }
 
-    pub fn build(self) -> GlobalHandler {
-        GlobalHandler::new(self.fps, self.handlers)
+    pub fn handler_with_policy(mut self, handler: Box<dyn Handler>, policy: FailurePolicy) -> Self {
+        self.handlers.push((handler, Some(policy)));
+        self
+    }
+
+    /// Policy for handlers registered without one; `FailurePolicy::Remove` by default.
+    pub fn failure_policy(mut self, policy: FailurePolicy) -> Self {
+        self.failure_policy = policy;
+        self
+    }
+
+    /// Called on the logging thread whenever a handler fails; prints to stderr by default.
+    pub fn on_error<F: Fn(&HandlerFailure) + Send + 'static>(mut self, f: F) -> Self {
+        self.on_error = Box::new(f);
+        self
+    }
+
+    /// Fails if the dispatcher is already driven by another `GlobalHandler`.
+    pub fn build(self) -> Result<GlobalHandler, AggregatedError> {
+        let slots: Vec<Slot> = self
+            .handlers
+            .into_iter()
+            .map(|(handler, policy)| {
+                Slot::new(handler, policy.unwrap_or_else(|| self.failure_policy.clone()))
+            })
+            .collect();
+        let ids = slots.iter().map(Slot::id).collect();
+
+        let dispatcher = self.dispatcher.unwrap_or_else(Dispatcher::global);
+        GlobalHandler::new(self.fps, dispatcher, Slots::new(slots, self.on_error), ids)
     }
 }
 
 pub struct GlobalHandler {
-    token: Arc<AtomicBool>,
-    handle: Option<JoinHandle<Result<(), SendSyncError>>>,
+    dispatcher: Dispatcher,
+    control: Sender<Command>,
+    handle: Option<JoinHandle<Result<(), AggregatedError>>>,
+    ids: Vec<HandlerId>,
+}
+
+pub(crate) enum Command {
+    Flush(Sender<Result<(), AggregatedError>>),
+    Shutdown(Sender<Result<(), AggregatedError>>),
+    Attach(Slot),
+    Detach(HandlerId, Sender<Result<(), AggregatedError>>),
+    Handlers(Sender<Vec<HandlerId>>),
+    SetLevel(HandlerId, Option<Level>, Sender<Result<(), AggregatedError>>),
+    SetFilter(HandlerId, Option<Filter>, Sender<Result<(), AggregatedError>>),
+    SetFps(u32),
+    Suspend(Sender<()>, Receiver<()>),
 }
 
-struct AggregatedError(Mutex<Vec<SendSyncError>>);
+thread_local! {
+    static HANDLER_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the current thread as one that runs handlers;
+/// it must never wait for the logging thread.
+pub(crate) fn mark_handler_thread() {
+    HANDLER_THREAD.with(|v| v.set(true));
+}
+
+pub(crate) fn is_handler_thread() -> bool {
+    HANDLER_THREAD.try_with(|v| v.get()).unwrap_or(true)
+}
+
+pub struct AggregatedError(Vec<SendSyncError>);
+
+impl AggregatedError {
+    pub(crate) fn new(errors: Vec<SendSyncError>) -> Self {
+        Self(errors)
+    }
+
+    pub(crate) fn single<E: Into<SendSyncError>>(error: E) -> Self {
+        Self(vec![error.into()])
+    }
+
+    pub(crate) fn merge(results: impl IntoIterator<Item = Result<(), AggregatedError>>) -> Result<(), Self> {
+        let errors: Vec<SendSyncError> = results
+            .into_iter()
+            .filter_map(Result::err)
+            .flat_map(|e| e.0)
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Self(errors))
+        }
+    }
+
+    pub fn errors(&self) -> &[SendSyncError] {
+        &self.0
+    }
+
+    pub fn into_errors(self) -> Vec<SendSyncError> {
+        self.0
+    }
+}
 
 impl Debug for AggregatedError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let lock = self.0.lock();
-
-        writeln!(f, "Aggregated error(s) (total {})", lock.len())?;
-        for i in 0..lock.len() {
-            writeln!(f, "- {}/{}: {}", i + 1, lock.len(), lock[i])?;
+        writeln!(f, "Aggregated error(s) (total {})", self.0.len())?;
+        for (i, e) in self.0.iter().enumerate() {
+            writeln!(f, "- {}/{}: {}", i + 1, self.0.len(), e)?;
         }
 
         Ok(())
@@ -66,17 +220,9 @@ impl Debug for AggregatedError {
 
 impl Display for AggregatedError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let lock = self.0.lock();
-
-        writeln!(f, "Aggregated error(s) (total {})", lock.len())?;
-        for i in 0..lock.len() {
-            writeln!(
-                f,
-                "=== Error Dump {}/{} ===\n{:?}",
-                i + 1,
-                lock.len(),
-                lock[i]
-            )?;
+        writeln!(f, "Aggregated error(s) (total {})", self.0.len())?;
+        for (i, e) in self.0.iter().enumerate() {
+            writeln!(f, "=== Error Dump {}/{} ===\n{:?}", i + 1, self.0.len(), e)?;
         }
 
         Ok(())
@@ -86,73 +232,141 @@ impl Display for AggregatedError {
 impl Error for AggregatedError {}
 
 impl GlobalHandler {
-    fn foreach<T, F: Fn(&T) -> Result<(), SendSyncError>>(
-        handlers: &mut Vec<T>,
-        f: F,
-    ) -> Result<(), AggregatedError> {
-        let mut begin = 0;
-
-        let mut errors: Vec<SendSyncError> = Vec::new();
-        while begin < handlers.len() {
-            'a: for i in begin..handlers.len() {
-                if let Err(e) = f(&handlers[i]) {
-                    errors.push(e);
-                    handlers.remove(i);
-                    #[allow(unused_assignments)]
-                    begin = i;
-                    break 'a;
-                }
-            }
+    const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
-            begin = handlers.len();
-        }
-        if !errors.is_empty() {
-            return Err(AggregatedError(Mutex::new(errors)));
+    // only what is queued now, so sustained producers can't keep it going forever
+    fn drain(dispatcher: &Dispatcher, handlers: &mut Slots) -> Result<(), AggregatedError> {
+        let mut results = Vec::new();
+        let queued = dispatcher.rx().len();
+        for event in dispatcher.rx().try_iter().take(queued) {
+            if let Err(e) = handlers.handle(&event) {
+                results.push(Err(e));
+            }
         }
 
-        Ok(())
+        AggregatedError::merge(results)
+    }
+
+    fn flush_all(dispatcher: &Dispatcher, handlers: &mut Slots) -> Result<(), AggregatedError> {
+        let drained = Self::drain(dispatcher, handlers);
+
+        let ticked = handlers.tick(&dispatcher.root(), None);
+        let flushed = handlers.foreach(true, |handler| handler.flush());
+
+        AggregatedError::merge([drained, ticked, flushed])
     }
 
     fn thread_loop(
         fps: u32,
-        token: Arc<AtomicBool>,
-        mut handlers: Vec<Box<dyn Handler>>,
-    ) -> Result<(), SendSyncError> {
-        let mut last_update = Instant::now();
-        let frame_duration = Duration::from_millis(1000 / fps as u64);
-
-        while token.load(Ordering::Acquire) {
-            loop {
-                match rx().try_recv() {
-                    Ok(event) => {
-                        Self::foreach(&mut handlers, |handler| handler.handle(&event))?;
+        dispatcher: Dispatcher,
+        control: Receiver<Command>,
+        mut handlers: Slots,
+    ) -> Result<(), AggregatedError> {
+        let mut frame_duration = Duration::from_millis(1000 / fps as u64);
+
+        // failures of handlers below are reported to the error callback by the slots,
+        // and recovered according to their policy; results are only kept for flushes
+        let root = dispatcher.root();
+        let _ = handlers.foreach(true, |handler| handler.on_start());
+
+        loop {
+            // live spans animate even without events, so they keep the frame clock running
+            let deadline = match handlers.next_tick(frame_duration, root.has_children()) {
+                Some(instant) => at(instant),
+                None => never(),
+            };
+
+            select_biased! {
+                recv(control) -> command => match command {
+                    Ok(Command::Flush(ack)) => {
+                        let _ = ack.send(Self::flush_all(&dispatcher, &mut handlers));
                     }
-                    Err(TryRecvError::Disconnected) => {
-                        return Err("Channel has been disconnected".into());
+                    Ok(Command::Shutdown(ack)) => {
+                        let flushed = Self::flush_all(&dispatcher, &mut handlers);
+                        let shutdown = handlers.foreach(true, |handler| handler.shutdown());
+                        let _ = ack.send(AggregatedError::merge([flushed, shutdown]));
+                        return Ok(());
                     }
-                    Err(TryRecvError::Empty) => {
-                        break;
+                    Ok(Command::Attach(slot)) => {
+                        let _ = handlers.attach(slot);
                     }
-                };
-            }
-            if last_update.elapsed() >= frame_duration {
-                let root = Span::root();
-                Self::foreach(&mut handlers, |handler| handler.tick(&root))?;
-                last_update = Instant::now();
-            }
+                    Ok(Command::Detach(id, ack)) => {
+                        let _ = ack.send(handlers.detach(id));
+                    }
+                    Ok(Command::Handlers(ack)) => {
+                        let _ = ack.send(handlers.ids());
+                    }
+                    Ok(Command::SetLevel(id, level, ack)) => {
+                        let _ = ack.send(handlers.set_level(id, level));
+                    }
+                    Ok(Command::SetFilter(id, filter, ack)) => {
+                        let _ = ack.send(handlers.set_filter(id, filter));
+                    }
+                    Ok(Command::SetFps(fps)) => {
+                        frame_duration = Duration::from_millis(1000 / fps as u64);
+                    }
+                    Ok(Command::Suspend(ack, resume)) => {
+                        let _ = Self::flush_all(&dispatcher, &mut handlers);
+                        let _ = handlers.foreach(true, |handler| handler.suspend());
+                        let _ = ack.send(());
 
-            thread::sleep(Duration::from_millis(10));
+                        // the caller owns the terminal until it drops its end;
+                        // events are still taken so that logging doesn't block meanwhile
+                        loop {
+                            select_biased! {
+                                recv(resume) -> _ => break,
+                                recv(dispatcher.rx()) -> event => match event {
+                                    Ok(event) => {
+                                        let _ = handlers.handle(&event);
+                                    }
+                                    Err(_) => break,
+                                },
+                            }
+                        }
+                        handlers.mark_dirty();
+                    }
+                    Err(_) => {
+                        return Err(AggregatedError::single("Control channel has been disconnected"));
+                    }
+                },
+                recv(deadline) -> _ => {
+                    let _ = handlers.tick(&root, Some(frame_duration));
+                }
+                recv(dispatcher.rx()) -> event => match event {
+                    Ok(event) => {
+                        let _ = handlers.handle(&event);
+                        let _ = Self::drain(&dispatcher, &mut handlers);
+                    }
+                    Err(_) => {
+                        return Err(AggregatedError::single("Channel has been disconnected"));
+                    }
+                },
+            }
         }
-
-        Ok(())
     }
 
-    fn new(fps: u32, handlers: Vec<Box<dyn Handler>>) -> Self {
-        let token = Arc::new(AtomicBool::new(true));
+    fn new(
+        fps: u32,
+        dispatcher: Dispatcher,
+        handlers: Slots,
+        ids: Vec<HandlerId>,
+    ) -> Result<Self, AggregatedError> {
+        // two threads on one channel would each get a random half of the events
+        if !dispatcher.claim() {
+            return Err(AggregatedError::single(
+                "Dispatcher is already driven by another GlobalHandler",
+            ));
+        }
 
-        let token_clone = token.clone();
-        let handle = thread::spawn(move || -> Result<(), SendSyncError> {
-            match Self::thread_loop(fps, token_clone, handlers) {
+        let (control, control_rx) = unbounded();
+        dispatcher.set_control(Some(HandlerControl::new(control.clone())));
+
+        let thread_dispatcher = dispatcher.clone();
+        let handle = thread::spawn(move || -> Result<(), AggregatedError> {
+            mark_handler_thread();
+            // only once the thread is gone, even if it outlived a timed out shutdown or panicked
+            let _release = Release(thread_dispatcher.clone());
+            match Self::thread_loop(fps, thread_dispatcher, control_rx, handlers) {
                 Ok(_) => Ok(()),
                 Err(e) => {
                     const MSG: &str = "[kairoi] logging thread panic; memory consumption with event channel won't be held";
@@ -165,24 +379,94 @@ impl GlobalHandler {
             }
         });
 
-        Self {
-            token,
+        Ok(Self {
+            dispatcher,
+            control,
             handle: Some(handle),
-        }
+            ids,
+        })
     }
 
     pub fn builder() -> GlobalHandlerBuilder {
         GlobalHandlerBuilder::new()
     }
+
+    fn join(&mut self) -> Result<(), AggregatedError> {
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+
+        match handle.join() {
+            Ok(result) => result,
+            Err(_) => Err(AggregatedError::single("Logging thread has panicked")),
+        }
+    }
+
+    pub fn dispatcher(&self) -> &Dispatcher {
+        &self.dispatcher
+    }
+
+    pub fn control(&self) -> HandlerControl {
+        HandlerControl::new(self.control.clone())
+    }
+
+    /// Ids of the handlers registered through the builder, in registration order,
+    /// to reconfigure them through `control`.
+    pub fn handler_ids(&self) -> &[HandlerId] {
+        &self.ids
+    }
+
+    /// Blocks until every event submitted before this call is handled,
+    /// a frame is drawn and all handlers are flushed.
+    pub fn flush(&self) -> Result<(), AggregatedError> {
+        self.control().flush()
+    }
+
+    fn shutdown_inner(&mut self, timeout: Duration) -> Result<(), AggregatedError> {
+        if self.handle.is_none() {
+            return Ok(());
+        }
+
+        let (ack, ack_rx) = unbounded();
+        let _ = self.control.send(Command::Shutdown(ack));
+
+        match ack_rx.recv_timeout(timeout) {
+            Ok(result) => AggregatedError::merge([result, self.join()]),
+            Err(RecvTimeoutError::Disconnected) => self.join(),
+            Err(RecvTimeoutError::Timeout) => {
+                // leave the thread detached; it's stuck in a handler,
+                // and releases the dispatcher once it gets to finish the shutdown
+                self.handle = None;
+                Err(AggregatedError::single(format!(
+                    "Logging thread didn't shut down within {timeout:?}; \
+                     the dispatcher stays claimed until it does"
+                )))
+            }
+        }
+    }
+
+    /// Drains the channel, flushes and shuts down all handlers, then stops the logging thread.
+    /// Errors raised by handlers while doing so are returned.
+    /// A thread still busy after `timeout` is left running, and keeps the dispatcher
+    /// from being driven by another `GlobalHandler` until it's done.
+    pub fn shutdown(mut self, timeout: Duration) -> Result<(), AggregatedError> {
+        self.shutdown_inner(timeout)
+    }
 }
 
-impl Drop for GlobalHandler {
+struct Release(Dispatcher);
+
+impl Drop for Release {
     fn drop(&mut self) {
-        sleep(Duration::from_millis(100));
+        self.0.set_control(None);
+        self.0.release();
+    }
+}
 
-        self.token.store(false, Ordering::Release);
-        if let Err(e) = self.handle.take().unwrap().join() {
-            std::panic::resume_unwind(e);
+impl Drop for GlobalHandler {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown_inner(Self::DEFAULT_SHUTDOWN_TIMEOUT) {
+            eprintln!("[kairoi] failed to shut down logging thread\n{e}");
         }
     }
 }
@@ -0,0 +1,456 @@
+use crate::{Id, Progress, SpanOrder, SpanRef};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Which spans stay visible when the tree doesn't fit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SpanPriority {
+    /// Longest running first.
+    Oldest,
+    /// Least progress per second first; spans without progress follow, oldest first.
+    Slowest,
+}
+
+/// `last` tells, for each level of nesting below the top, whether the row there
+/// is the last one drawn below its parent.
+pub(crate) enum Row {
+    Span {
+        span: SpanRef,
+        last: Vec<bool>,
+        // whether rows are drawn below it
+        nested: bool,
+    },
+    /// Spans hidden below a shown span, or the root.
+    More {
+        last: Vec<bool>,
+        count: usize,
+        progress: Option<Progress>,
+    },
+}
+
+pub(crate) struct Limits {
+    pub max_depth: Option<usize>,
+    pub max_spans: Option<usize>,
+    pub lines: usize,
+    pub priority: SpanPriority,
+    pub order: SpanOrder,
+}
+
+struct Entry {
+    span: SpanRef,
+    parent: Option<usize>,
+    // lines shown below the span itself
+    extra: usize,
+    descendants: usize,
+}
+
+struct Tree {
+    entries: Vec<Entry>,
+    // children of each entry; the last one holds the children of the root
+    children: Vec<Vec<usize>>,
+    // finished spans by their parent, yet to be placed
+    lingering: HashMap<Id, Vec<SpanRef>>,
+    max_depth: Option<usize>,
+    order: SpanOrder,
+}
+
+impl Tree {
+    fn new(
+        root: &SpanRef,
+        lingering: &[SpanRef],
+        max_depth: Option<usize>,
+        order: SpanOrder,
+    ) -> Self {
+        let mut tree = Self {
+            entries: Vec::new(),
+            children: Vec::new(),
+            lingering: HashMap::new(),
+            max_depth,
+            order,
+        };
+        for span in lingering {
+            let parent = span.parent().map_or(root.id(), |parent| parent.id());
+            tree.lingering.entry(parent).or_default().push(span.clone());
+        }
+
+        tree.collect(root, None);
+
+        // parents that have gone as well; ancestors come first, as ids only grow
+        while let Some(&id) = tree.lingering.keys().min() {
+            let spans = tree.lingering.remove(&id).unwrap_or_default();
+            tree.push(spans, None);
+        }
+
+        tree.children.resize_with(tree.entries.len() + 1, Vec::new);
+        for (i, entry) in tree.entries.iter().enumerate() {
+            let parent = entry.parent.unwrap_or(tree.entries.len());
+            tree.children[parent].push(i);
+        }
+
+        // children always come after their parent
+        for i in (0..tree.entries.len()).rev() {
+            if let Some(parent) = tree.entries[i].parent {
+                tree.entries[parent].descendants += tree.entries[i].descendants + 1;
+            }
+        }
+        tree
+    }
+
+    fn collect(&mut self, span: &SpanRef, parent: Option<usize>) {
+        let mut children: Vec<_> = span.children().collect();
+        children.extend(self.lingering.remove(&span.id()).unwrap_or_default());
+        self.push(children, parent);
+    }
+
+    fn push(&mut self, mut spans: Vec<SpanRef>, parent: Option<usize>) {
+        // ids follow the order spans were entered in
+        spans.sort_by_key(|span| span.id());
+        self.order.sort(&mut spans);
+
+        for span in spans {
+            if self.max_depth.is_some_and(|max| span.depth() > max) {
+                continue;
+            }
+
+            let i = self.entries.len();
+            self.entries.push(Entry {
+                span: span.clone(),
+                parent,
+                extra: 0,
+                descendants: 0,
+            });
+            self.collect(&span, Some(i));
+        }
+    }
+
+    fn root(&self) -> usize {
+        self.entries.len()
+    }
+
+    // nearest shown ancestor, or the root
+    fn owner(&self, i: usize, shown: &[bool]) -> usize {
+        let mut parent = self.entries[i].parent;
+        while let Some(p) = parent {
+            if shown[p] {
+                return p;
+            }
+            parent = self.entries[p].parent;
+        }
+        self.root()
+    }
+
+}
+
+/// Spans picked so far, and how many lines they take along with the `More` rows.
+struct Selection {
+    shown: Vec<bool>,
+    // hidden spans by their nearest shown ancestor, or the root
+    hidden: Vec<usize>,
+    lines: usize,
+}
+
+impl Selection {
+    fn new(tree: &Tree) -> Self {
+        let n = tree.entries.len();
+        let mut hidden = vec![0; n + 1];
+        hidden[n] = n;
+
+        Self {
+            shown: vec![false; n],
+            hidden,
+            lines: usize::from(n > 0),
+        }
+    }
+
+    // the parent must be shown already; its hidden descendants below `i` move to `i`
+    fn show(&mut self, tree: &Tree, i: usize) {
+        let entry = &tree.entries[i];
+        let owner = entry.parent.unwrap_or(tree.root());
+
+        self.shown[i] = true;
+        self.hidden[owner] -= 1 + entry.descendants;
+        self.hidden[i] = entry.descendants;
+
+        self.lines += 1 + entry.extra + usize::from(entry.descendants > 0);
+        if self.hidden[owner] == 0 {
+            self.lines -= 1;
+        }
+    }
+}
+
+fn compare(a: &SpanRef, b: &SpanRef, priority: SpanPriority, now: SystemTime) -> Ordering {
+    let (a, b) = (a.value(), b.value());
+    let by_age = a.timestamp().cmp(&b.timestamp());
+
+    match priority {
+        SpanPriority::Oldest => by_age,
+        SpanPriority::Slowest => {
+            let rate = |span: &crate::Span| {
+                let p = span.progress().filter(|p| p.total() > 0)?;
+                let elapsed = now.duration_since(span.timestamp()).unwrap_or_default();
+                Some(p.progress() as f64 / p.total() as f64 / elapsed.as_secs_f64().max(0.001))
+            };
+
+            match (rate(&a), rate(&b)) {
+                (Some(x), Some(y)) => x.total_cmp(&y).then(by_age),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => by_age,
+            }
+        }
+    }
+}
+
+/// Rows of the span tree in `limits.lines` lines at most, with at most `limits.max_spans` spans;
+/// `extra` tells how many lines are shown below a span besides its own.
+///
+/// Spans are picked by `limits.priority` along with their ancestors;
+/// the rest are folded into one `More` row under their nearest shown ancestor.
+/// `lingering` spans have ended, but are shown under their parent for a while.
+pub(crate) fn collapse(
+    root: &SpanRef,
+    lingering: &[SpanRef],
+    limits: &Limits,
+    extra: impl Fn(&SpanRef) -> usize,
+) -> Vec<Row> {
+    let mut tree = Tree::new(root, lingering, limits.max_depth, limits.order);
+    for entry in &mut tree.entries {
+        entry.extra = extra(&entry.span);
+    }
+
+    let n = tree.entries.len();
+    let lines = limits.lines;
+    let max_spans = limits.max_spans.unwrap_or(usize::MAX);
+
+    let all: usize = tree.entries.iter().map(|entry| 1 + entry.extra).sum();
+    let fits = n <= max_spans && all <= lines;
+    let mut shown = vec![fits; n];
+    if !fits {
+        let now = SystemTime::now();
+        let mut ranked: Vec<usize> = (0..n).collect();
+        ranked.sort_by(|&a, &b| {
+            compare(&tree.entries[a].span, &tree.entries[b].span, limits.priority, now)
+        });
+
+        let mut selection = Selection::new(&tree);
+        let mut count = 0;
+        for i in ranked {
+            let mut added = Vec::new();
+            let mut next = Some(i);
+            while let Some(j) = next.filter(|&j| !selection.shown[j]) {
+                added.push(j);
+                next = tree.entries[j].parent;
+            }
+
+            count += added.len();
+            if count > max_spans {
+                break;
+            }
+            // ancestors first
+            for &j in added.iter().rev() {
+                selection.show(&tree, j);
+            }
+            if selection.lines > lines {
+                for j in added {
+                    selection.shown[j] = false;
+                }
+                break;
+            }
+        }
+        shown = selection.shown;
+    }
+
+    let mut hidden: Vec<(usize, Option<Progress>)> = vec![(0, None); n + 1];
+    for i in (0..n).filter(|&i| !shown[i]) {
+        let (count, progress) = &mut hidden[tree.owner(i, &shown)];
+        *count += 1;
+        if let Some(p) = tree.entries[i].span.value().progress() {
+            let sum = progress.unwrap_or(Progress::new(0, 0));
+            *progress = Some(Progress::new(sum.total() + p.total(), sum.progress() + p.progress()));
+        }
+    }
+
+    let mut rows = Vec::new();
+    render(&tree, tree.root(), &[], &shown, &hidden, &mut rows);
+    rows
+}
+
+fn render(
+    tree: &Tree,
+    node: usize,
+    last: &[bool],
+    shown: &[bool],
+    hidden: &[(usize, Option<Progress>)],
+    rows: &mut Vec<Row>,
+) {
+    let children: Vec<usize> = tree.children[node].iter().copied().filter(|&c| shown[c]).collect();
+    let (count, progress) = hidden[node];
+
+    // top-level rows take no glyph
+    let below = |is_last: bool| {
+        let mut last = last.to_vec();
+        if node != tree.root() {
+            last.push(is_last);
+        }
+        last
+    };
+
+    for (i, &child) in children.iter().enumerate() {
+        let last = below(i + 1 == children.len() && count == 0);
+        let nested = hidden[child].0 > 0 || tree.children[child].iter().any(|&c| shown[c]);
+        rows.push(Row::Span {
+            span: tree.entries[child].span.clone(),
+            last: last.clone(),
+            nested,
+        });
+        render(tree, child, &last, shown, hidden, rows);
+    }
+
+    if count > 0 {
+        rows.push(Row::More {
+            last: below(true),
+            count,
+            progress,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Node, Span};
+    use std::sync::Arc;
+
+    fn root() -> SpanRef {
+        Arc::new(Node::new(Span::default()))
+    }
+
+    fn add(parent: &SpanRef, name: &str, progress: Option<(u64, u64)>) -> SpanRef {
+        let mut span = Span::default().with_name(name.to_string());
+        if let Some((total, progress)) = progress {
+            span = span.with_progress(Progress::new(total, progress));
+        }
+        parent.add(Node::new(span))
+    }
+
+    fn limits(lines: usize, max_spans: Option<usize>, priority: SpanPriority) -> Limits {
+        Limits {
+            max_depth: None,
+            max_spans,
+            lines,
+            priority,
+            order: SpanOrder::Inserted,
+        }
+    }
+
+    // one line per row, indented by nesting
+    fn draw(rows: &[Row]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                Row::Span { span, last, .. } => {
+                    format!("{}{}", "  ".repeat(last.len()), span.value().name())
+                }
+                Row::More {
+                    last,
+                    count,
+                    progress,
+                } => {
+                    let progress = progress
+                        .map(|p| format!(" {}/{}", p.progress(), p.total()))
+                        .unwrap_or_default();
+                    format!("{}+{count}{progress}", "  ".repeat(last.len()))
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shows_everything_that_fits() {
+        let root = root();
+        let a = add(&root, "a", None);
+        add(&a, "a1", None);
+        add(&root, "b", None);
+
+        let rows = collapse(&root, &[], &limits(3, None, SpanPriority::Oldest), |_| 0);
+        assert_eq!(draw(&rows), ["a", "  a1", "b"]);
+    }
+
+    #[test]
+    fn keeps_to_the_lines_given() {
+        let root = root();
+        for name in ["a", "b", "c", "d", "e"] {
+            add(&root, name, None);
+        }
+
+        let rows = collapse(&root, &[], &limits(3, None, SpanPriority::Oldest), |_| 0);
+        assert_eq!(draw(&rows), ["a", "b", "+3"]);
+
+        // lines below a span count as well
+        let rows = collapse(&root, &[], &limits(4, None, SpanPriority::Oldest), |_| 1);
+        assert_eq!(draw(&rows), ["a", "+4"]);
+    }
+
+    #[test]
+    fn rolls_back_spans_that_overflow_along_with_their_ancestors() {
+        let root = root();
+        let a = add(&root, "a", None);
+        let a1 = add(&a, "a1", None);
+        // slowest, so it's picked first, and takes its ancestors along
+        add(&a1, "a2", Some((100, 1)));
+        add(&root, "b", None);
+
+        // b takes the line of the `More` row it replaces
+        let rows = collapse(&root, &[], &limits(4, None, SpanPriority::Slowest), |_| 0);
+        assert_eq!(draw(&rows), ["a", "  a1", "    a2", "b"]);
+
+        let rows = collapse(&root, &[], &limits(3, None, SpanPriority::Slowest), |_| 0);
+        assert_eq!(draw(&rows), ["+4 1/100"]);
+    }
+
+    #[test]
+    fn cuts_off_at_max_spans() {
+        let root = root();
+        for name in ["a", "b", "c", "d", "e"] {
+            add(&root, name, None);
+        }
+
+        let rows = collapse(&root, &[], &limits(100, Some(2), SpanPriority::Oldest), |_| 0);
+        assert_eq!(draw(&rows), ["a", "b", "+3"]);
+
+        let root = self::root();
+        let a = add(&root, "a", None);
+        let a1 = add(&a, "a1", None);
+        add(&a1, "a2", Some((100, 1)));
+
+        // a2 would bring its two ancestors along
+        let rows = collapse(&root, &[], &limits(100, Some(2), SpanPriority::Slowest), |_| 0);
+        assert_eq!(draw(&rows), ["+3 1/100"]);
+    }
+
+    #[test]
+    fn sums_up_hidden_spans() {
+        let root = root();
+        let p = add(&root, "p", None);
+        add(&p, "c1", Some((4, 1)));
+        add(&p, "c2", Some((6, 2)));
+        add(&p, "c3", None);
+        add(&root, "q", Some((5, 5)));
+        add(&root, "r", None);
+
+        let rows = collapse(&root, &[], &limits(100, Some(1), SpanPriority::Oldest), |_| 0);
+        assert_eq!(draw(&rows), ["p", "  +3 3/10", "+2 5/5"]);
+
+        match &rows[..] {
+            [
+                Row::Span { nested: true, .. },
+                Row::More { last: below, .. },
+                Row::More { last: top, .. },
+            ] => {
+                assert_eq!(below, &[true]);
+                assert!(top.is_empty());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,93 +1,563 @@
 use crate::error::SendSyncError;
 use crate::format::Write;
-use crate::format::{DefaultFormatter, FormatterSet, Writer};
-use crate::{Event, GlobalHandlerBuilder, Handler, Log, SpanRef};
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::io::stdout;
+use crate::handlers::collapse::{collapse, Limits, Row};
+use crate::format::{format_duration, DefaultFormatter, FormatterSet, Writer};
+use crate::terminal::truncate;
+use crate::{Event, GlobalHandlerBuilder, Id, SpanOrder, SpanPriority, Handler, Log, SpanRef, Terminal};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::io::{stderr, stdout, IsTerminal};
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use terminal_size::{terminal_size_of, Height, Width};
 
 pub trait AddConsoleHandler<T> {
     fn console_handler(self) -> T;
+
+    /// e.g. `.console_handler_with(|console| console.stderr().max_depth(2))`
+    fn console_handler_with<F>(self, f: F) -> T
+    where
+        F: FnOnce(ConsoleHandlerBuilder) -> ConsoleHandlerBuilder;
 }
 
 impl AddConsoleHandler<GlobalHandlerBuilder> for GlobalHandlerBuilder {
     fn console_handler(self) -> Self {
         self.handler(Box::new(ConsoleHandler::new()))
     }
+
+    fn console_handler_with<F>(self, f: F) -> Self
+    where
+        F: FnOnce(ConsoleHandlerBuilder) -> ConsoleHandlerBuilder,
+    {
+        self.handler(Box::new(f(ConsoleHandler::builder()).build()))
+    }
+}
+
+/// What the console handler shows.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ConsoleLayout {
+    /// Logs, and spans once they end if `completion_lines` is set, but no live tree;
+    /// with `JsonFormatter` or `LogfmtFormatter`, every line is a record of its own.
+    Logs,
+    Tree,
+    Both,
+}
+
+enum Output {
+    Stdout,
+    Stderr,
+    Writer(RefCell<Box<dyn io::Write + Send>>),
+}
+
+impl Output {
+    fn is_terminal(&self) -> bool {
+        match self {
+            Output::Stdout => stdout().is_terminal(),
+            Output::Stderr => stderr().is_terminal(),
+            Output::Writer(_) => false,
+        }
+    }
+
+    fn size(&self) -> Option<(usize, usize)> {
+        let (Width(w), Height(h)) = match self {
+            Output::Stdout => terminal_size_of(stdout())?,
+            Output::Stderr => terminal_size_of(stderr())?,
+            Output::Writer(_) => return None,
+        };
+
+        Some((w as usize, h as usize))
+    }
+
+    fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        fn write_to(w: &mut dyn io::Write, bytes: &[u8]) -> io::Result<()> {
+            w.write_all(bytes)?;
+            w.flush()
+        }
+
+        match self {
+            Output::Stdout => write_to(&mut stdout().lock(), bytes),
+            Output::Stderr => write_to(&mut stderr().lock(), bytes),
+            Output::Writer(w) => write_to(w.borrow_mut().as_mut(), bytes),
+        }
+    }
+}
+
+pub struct ConsoleHandlerBuilder {
+    output: Output,
+    terminal: Option<Terminal>,
+    formatter: Option<Box<dyn FormatterSet>>,
+    layout: ConsoleLayout,
+    max_depth: Option<usize>,
+    max_spans: Option<usize>,
+    priority: SpanPriority,
+    order: SpanOrder,
+    linger: Duration,
+    completion_lines: bool,
+    group_logs: Option<usize>,
+}
+
+impl ConsoleHandlerBuilder {
+    fn new() -> Self {
+        Self {
+            output: Output::Stdout,
+            terminal: None,
+            formatter: None,
+            layout: ConsoleLayout::Both,
+            max_depth: None,
+            max_spans: None,
+            priority: SpanPriority::Oldest,
+            order: SpanOrder::Inserted,
+            linger: Duration::ZERO,
+            completion_lines: false,
+            group_logs: None,
+        }
+    }
+
+    pub fn stdout(mut self) -> Self {
+        self.output = Output::Stdout;
+        self
+    }
+
+    pub fn stderr(mut self) -> Self {
+        self.output = Output::Stderr;
+        self
+    }
+
+    /// Writes to `writer`, which is treated as a non-terminal unless `terminal` says otherwise.
+    pub fn writer<W: io::Write + Send + 'static>(mut self, writer: W) -> Self {
+        self.output = Output::Writer(RefCell::new(Box::new(writer)));
+        self
+    }
+
+    /// Overrides what is detected from the output stream and the environment.
+    pub fn terminal(mut self, terminal: Terminal) -> Self {
+        self.terminal = Some(terminal);
+        self
+    }
+
+    /// Defaults to `DefaultFormatter`, colored if the terminal supports it.
+    pub fn formatter(mut self, formatter: Box<dyn FormatterSet>) -> Self {
+        self.formatter = Some(formatter);
+        self
+    }
+
+    pub fn layout(mut self, layout: ConsoleLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Spans nested deeper than `depth` are not shown; top-level spans are at depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Shows at most `spans` spans; the rest are folded into `… N more running` lines,
+    /// as are spans that don't fit the height of the terminal.
+    pub fn max_spans(mut self, spans: usize) -> Self {
+        self.max_spans = Some(spans);
+        self
+    }
+
+    /// Which spans stay visible when not all of them fit; `SpanPriority::Oldest` by default.
+    pub fn priority(mut self, priority: SpanPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Order of sibling spans; the order they were entered in by default.
+    pub fn order(mut self, order: SpanOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Keeps ended spans in the tree as `✔ name [3.2s]` or `✘ name (failed)` for `linger`;
+    /// they disappear right away by default.
+    pub fn linger(mut self, linger: Duration) -> Self {
+        self.linger = linger;
+        self
+    }
+
+    /// Prints a line for every span that ends, like `finished codegen in 3.2s`
+    /// with the default formatter, or the span as a record with `JsonFormatter` and
+    /// `LogfmtFormatter`; see `FormatterSet::format_completion`.
+    pub fn completion_lines(mut self, enabled: bool) -> Self {
+        self.completion_lines = enabled;
+        self
+    }
+
+    /// Shows the last `lines` logs of each span beneath it instead of in the scrollback;
+    /// all logs of a span are printed there as a block once it ends, or once it holds 1000 of them.
+    /// Logs outside of any span are printed right away.
+    pub fn group_logs(mut self, lines: usize) -> Self {
+        self.group_logs = Some(lines);
+        self
+    }
+
+    pub fn build(self) -> ConsoleHandler {
+        let terminal = self
+            .terminal
+            .unwrap_or_else(|| Terminal::detect(self.output.is_terminal()));
+        let formatter = self
+            .formatter
+            .unwrap_or_else(|| Box::new(DefaultFormatter::with_color(terminal.color())));
+
+        ConsoleHandler {
+            log_queue: RefCell::new(VecDeque::new()),
+            lingering: RefCell::new(Vec::new()),
+            groups: RefCell::new(HashMap::new()),
+            drawn: RefCell::new(Vec::new()),
+            output: self.output,
+            formatter,
+            terminal,
+            layout: self.layout,
+            max_depth: self.max_depth,
+            max_spans: self.max_spans,
+            priority: self.priority,
+            order: self.order,
+            linger: self.linger,
+            completion_lines: self.completion_lines,
+            group_logs: self.group_logs,
+            last_summary: Cell::new(Instant::now()),
+        }
+    }
+}
+
+enum Pending {
+    Log(Arc<Log>),
+    Ended(SpanRef),
 }
 
 pub struct ConsoleHandler {
-    log_queue: RefCell<VecDeque<Arc<Log>>>,
-    cursor_saved: RefCell<bool>,
+    log_queue: RefCell<VecDeque<Pending>>,
+    // ended spans and when they ended
+    lingering: RefCell<Vec<(SpanRef, Instant)>>,
+    // logs held back until their span ends
+    groups: RefCell<HashMap<Id, Vec<Arc<Log>>>>,
+    // lines of the live region currently on screen, above the cursor
+    drawn: RefCell<Vec<String>>,
+    output: Output,
     formatter: Box<dyn FormatterSet>,
+    terminal: Terminal,
+    layout: ConsoleLayout,
+    max_depth: Option<usize>,
+    max_spans: Option<usize>,
+    priority: SpanPriority,
+    order: SpanOrder,
+    linger: Duration,
+    completion_lines: bool,
+    group_logs: Option<usize>,
+    last_summary: Cell<Instant>,
 }
 
 impl ConsoleHandler {
+    /// Interval between progress summaries when the live tree can't be drawn.
+    const SUMMARY_INTERVAL: Duration = Duration::from_secs(5);
+    const SUMMARY_SPANS: usize = 5;
+    const FALLBACK_SIZE: (usize, usize) = (80, 24);
+    /// Logs held back for one span before they are printed anyway.
+    const GROUP_CAPACITY: usize = 1_000;
+
+    /// Draws the live span tree on a terminal; prints plain lines and periodic
+    /// progress summaries when stdout is piped or `TERM=dumb`.
+    /// `NO_COLOR` and `CLICOLOR_FORCE` only decide whether colors are written.
     pub fn new() -> Self {
-        Self {
-            log_queue: RefCell::new(VecDeque::new()),
-            cursor_saved: RefCell::new(false),
-            formatter: Box::new(DefaultFormatter::new()),
-        }
+        Self::builder().build()
+    }
+
+    pub fn with_terminal(terminal: Terminal) -> Self {
+        Self::builder().terminal(terminal).build()
+    }
+
+    pub fn builder() -> ConsoleHandlerBuilder {
+        ConsoleHandlerBuilder::new()
+    }
+}
+
+impl Default for ConsoleHandler {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl ConsoleHandler {
-    fn print(
-        &self,
-        writer: &mut Writer,
-        span: &SpanRef,
-        depth: usize,
-    ) -> Result<(), SendSyncError> {
-        if depth > 0 {
-            self.formatter.format(writer, span)?;
-        }
-
-        for child in span.children() {
-            if let Err(e) = self.print(writer, &child, depth + 1) {
-                return Err(e);
+    fn shows_logs(&self) -> bool {
+        self.layout != ConsoleLayout::Tree
+    }
+
+    fn shows_tree(&self) -> bool {
+        self.layout != ConsoleLayout::Logs
+    }
+
+    fn print(&self, lines: &mut Vec<String>, row: &Row) -> Result<(), SendSyncError> {
+        let mut line = String::new();
+        match row {
+            Row::Span { span, last, .. } => {
+                self.formatter.format_tree_span(&mut Writer::Fmt(&mut line), span, last)?
+            }
+            Row::More {
+                last,
+                count,
+                progress,
+            } => {
+                let (gray, reset) = if self.terminal.color() {
+                    ("\x1b[90m", "\x1b[0m")
+                } else {
+                    ("", "")
+                };
+                let indent = self.formatter.tree_indent(last, true);
+                line = format!("{indent}{gray}… {count} more running");
+                if let Some(p) = progress {
+                    line.push_str(&format!(" ({}/{})", p.progress(), p.total()));
+                }
+                line.push_str(reset);
             }
         }
+        lines.extend(line.lines().map(str::to_owned));
 
         Ok(())
     }
+
+    // Lines stay a column short of the width, as writing the last column defers the wrap
+    // differently across terminals; the region stays below the height, as lines scrolled
+    // off the screen can't be reached by moving the cursor up.
+    fn frame(&self, root: &SpanRef) -> Result<Vec<String>, SendSyncError> {
+        let (width, height) = self.output.size().unwrap_or(Self::FALLBACK_SIZE);
+        let (width, height) = (width.saturating_sub(1), height.saturating_sub(1));
+
+        let lingering: Vec<SpanRef> = {
+            let mut lingering = self.lingering.borrow_mut();
+            lingering.retain(|(_, ended)| ended.elapsed() < self.linger);
+            lingering.iter().map(|(span, _)| span.clone()).collect()
+        };
+
+        let limits = Limits {
+            max_depth: self.max_depth,
+            max_spans: self.max_spans,
+            lines: height,
+            priority: self.priority,
+            order: self.order,
+        };
+        let groups = self.groups.borrow();
+        let tail = |span: &SpanRef| {
+            let logs = groups.get(&span.id()).map_or(&[][..], Vec::as_slice);
+            &logs[logs.len().saturating_sub(self.group_logs.unwrap_or(0))..]
+        };
+
+        let mut lines = Vec::new();
+        for row in collapse(root, &lingering, &limits, |span| tail(span).len()) {
+            self.print(&mut lines, &row)?;
+
+            if let Row::Span { span, last, nested } = &row {
+                let mut last = last.clone();
+                last.push(!nested);
+                let indent = self.formatter.tree_indent(&last, false);
+                for log in tail(span) {
+                    let mut line = String::new();
+                    self.formatter.format(&mut Writer::Fmt(&mut line), log.deref())?;
+                    lines.extend(line.lines().map(|line| format!("{indent}{line}")));
+                }
+            }
+        }
+
+        // formatters may take more than a line per span
+        if lines.len() > height {
+            let hidden = lines.len() - height + 1;
+            lines.truncate(height.saturating_sub(1));
+            lines.push(format!("… {hidden} more lines"));
+        }
+        for line in &mut lines {
+            if let Cow::Owned(cut) = truncate(line, width) {
+                *line = cut;
+            }
+        }
+
+        Ok(lines)
+    }
+
+    fn print_pending(&self, out: &mut String, pending: Pending) -> Result<(), SendSyncError> {
+        match pending {
+            Pending::Log(log) => self.formatter.format(&mut Writer::Fmt(out), log.deref()),
+            Pending::Ended(span) => self.formatter.format_completion(&mut Writer::Fmt(out), &span),
+        }
+    }
+
+    fn erase(drawn: &[String], out: &mut String) {
+        if !drawn.is_empty() {
+            out.push_str(&format!("\r\x1b[{}A", drawn.len()));
+        }
+    }
+
+    // one line instead of a tree that can't be redrawn
+    fn summarize(&self, writer: &mut Writer, root: &SpanRef) -> Result<(), SendSyncError> {
+        if self.last_summary.get().elapsed() < Self::SUMMARY_INTERVAL {
+            return Ok(());
+        }
+        self.last_summary.set(Instant::now());
+
+        let spans = root.find_all(|node| {
+            node.depth() > 0 && !self.max_depth.is_some_and(|max| node.depth() > max)
+        });
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let mut line = format!("[kairoi] {} running: ", spans.len());
+        for (i, span) in spans.iter().take(Self::SUMMARY_SPANS).enumerate() {
+            if i > 0 {
+                line.push_str(", ");
+            }
+
+            let value = span.value();
+            line.push_str(value.name());
+            if let Some(p) = value.progress() {
+                line.push_str(&format!(" ({}/{})", p.progress(), p.total()));
+            }
+            let elapsed = value.timestamp().elapsed().unwrap_or_default();
+            line.push_str(&format!(" [{}]", format_duration(elapsed)));
+        }
+        if spans.len() > Self::SUMMARY_SPANS {
+            line.push_str(&format!(", … {} more", spans.len() - Self::SUMMARY_SPANS));
+        }
+
+        writeln!(writer, "{line}")
+    }
 }
 
 impl Handler for ConsoleHandler {
     fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
         match event {
-            Event::Log(log) => self.log_queue.borrow_mut().push_back(log.clone()),
+            Event::Log(log) => {
+                if !self.shows_logs() {
+                    return Ok(());
+                }
+
+                if self.group_logs.is_some() && log.span().depth() > 0 {
+                    let mut groups = self.groups.borrow_mut();
+                    let group = groups.entry(log.span().id()).or_default();
+                    group.push(log.clone());
+                    if group.len() >= Self::GROUP_CAPACITY {
+                        let mut queue = self.log_queue.borrow_mut();
+                        queue.extend(group.drain(..).map(Pending::Log));
+                    }
+                } else {
+                    self.log_queue.borrow_mut().push_back(Pending::Log(log.clone()))
+                }
+            }
             Event::SpanBegin(_) => {}
-            Event::SpanEnd(_) => {}
+            Event::SpanEnd(span) => {
+                if let Some(logs) = self.groups.borrow_mut().remove(&span.id()) {
+                    let mut queue = self.log_queue.borrow_mut();
+                    queue.extend(logs.into_iter().map(Pending::Log));
+                }
+                if self.completion_lines {
+                    self.log_queue.borrow_mut().push_back(Pending::Ended(span.clone()));
+                }
+                if !self.linger.is_zero() && self.shows_tree() && self.terminal.interactive() {
+                    self.lingering.borrow_mut().push((span.clone(), Instant::now()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // spans that never ended still hold their logs
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        let mut groups: Vec<_> = self.groups.borrow_mut().drain().collect();
+        if groups.is_empty() {
+            return Ok(());
+        }
+        groups.sort_by_key(|(id, _)| *id);
+
+        self.suspend()?;
+        let mut out = String::new();
+        for log in groups.into_iter().flat_map(|(_, logs)| logs) {
+            self.formatter.format(&mut Writer::Fmt(&mut out), log.deref())?;
+        }
+        self.output.write(out.as_bytes())?;
+
+        Ok(())
+    }
+
+    // until the last lingering span is gone
+    fn animating(&self) -> bool {
+        !self.lingering.borrow().is_empty()
+    }
+
+    fn suspend(&self) -> Result<(), SendSyncError> {
+        let mut drawn = self.drawn.borrow_mut();
+        if !drawn.is_empty() {
+            let mut out = String::new();
+            Self::erase(&drawn, &mut out);
+            out.push_str("\x1b[J");
+            drawn.clear();
+
+            self.output.write(out.as_bytes())?;
         }
 
         Ok(())
     }
 
     fn tick(&self, root: &SpanRef) -> Result<(), SendSyncError> {
-        let mut lock = stdout().lock();
+        let mut out = String::new();
 
-        let mut writer = Writer::Io(&mut lock);
+        if !self.terminal.interactive() {
+            while let Some(pending) = self.log_queue.borrow_mut().pop_front() {
+                self.print_pending(&mut out, pending)?
+            }
+            if self.shows_tree() {
+                self.summarize(&mut Writer::Fmt(&mut out), root)?;
+            }
 
-        if *self.cursor_saved.borrow() {
-            write!(writer, "\x1b[u\x1b[J")?;
+            if !out.is_empty() {
+                self.output.write(out.as_bytes())?;
+            }
+            return Ok(());
         }
 
-        while let Some(log) = self.log_queue.borrow_mut().pop_front() {
-            self.formatter.format(&mut writer, log.deref())?
+        let frame = if self.shows_tree() {
+            self.frame(root)?
+        } else {
+            Vec::new()
+        };
+        let mut drawn = self.drawn.borrow_mut();
+        let mut queue = self.log_queue.borrow_mut();
+        if queue.is_empty() && *drawn == frame {
+            return Ok(());
         }
 
-        {
-            write!(writer, "\x1b[s")?;
-            self.cursor_saved.replace(true);
-            self.print(&mut writer, root, 0)?;
+        out.push_str("\x1b[?25l");
+        Self::erase(&drawn, &mut out);
+
+        if !queue.is_empty() {
+            // logs go above the region, so every line below them moves
+            out.push_str("\x1b[J");
+            drawn.clear();
+            while let Some(pending) = queue.pop_front() {
+                self.print_pending(&mut out, pending)?
+            }
         }
 
-        drop(writer);
+        for (i, line) in frame.iter().enumerate() {
+            if drawn.get(i) == Some(line) {
+                out.push_str("\x1b[B");
+            } else {
+                out.push_str("\x1b[2K");
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        if frame.len() < drawn.len() {
+            out.push_str("\x1b[J");
+        }
+        out.push_str("\x1b[?25h");
+        *drawn = frame;
 
-        std::io::Write::flush(&mut lock)?;
+        self.output.write(out.as_bytes())?;
 
         Ok(())
     }
@@ -0,0 +1,230 @@
+use crate::error::SendSyncError;
+use crate::handler::mark_handler_thread;
+use crate::{Event, GlobalHandlerBuilder, Handler, SpanRef};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Overflow {
+    /// Wait for the worker; the logging thread stalls as long as the queue is full.
+    Block,
+    /// Discard the event that doesn't fit.
+    DropNewest,
+    /// Discard the oldest queued event to make room.
+    DropOldest,
+}
+
+pub trait AddIsolatedHandler<T> {
+    fn isolated_handler(self, handler: Box<dyn Handler>, capacity: usize, overflow: Overflow) -> T;
+}
+
+impl AddIsolatedHandler<GlobalHandlerBuilder> for GlobalHandlerBuilder {
+    fn isolated_handler(self, handler: Box<dyn Handler>, capacity: usize, overflow: Overflow) -> Self {
+        self.handler(Box::new(IsolatedHandler::new(handler, capacity, overflow)))
+    }
+}
+
+enum Message {
+    Event(Event),
+    Tick(SpanRef),
+    Flush(Sender<Result<(), SendSyncError>>),
+    Suspend(Sender<Result<(), SendSyncError>>),
+    Shutdown(Sender<Result<(), SendSyncError>>),
+}
+
+/// Runs the inner handler on a dedicated thread, fed by a bounded queue.
+///
+/// Errors of the inner handler are reported on the next `handle`/`tick`,
+/// so the failure policy of the global handler still applies.
+pub struct IsolatedHandler {
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+    errors: Receiver<SendSyncError>,
+    tick_pending: Arc<AtomicBool>,
+    // last answer of the inner handler, which only the worker may ask
+    animating: Arc<AtomicBool>,
+    overflow: Overflow,
+    wants_tick: bool,
+    tick_interval: Option<Duration>,
+    handle: RefCell<Option<JoinHandle<()>>>,
+}
+
+impl IsolatedHandler {
+    pub fn new(handler: Box<dyn Handler>, capacity: usize, overflow: Overflow) -> Self {
+        let (tx, rx) = bounded(capacity.max(1));
+        let (error_tx, errors) = unbounded();
+        let tick_pending = Arc::new(AtomicBool::new(false));
+        let animating = Arc::new(AtomicBool::new(false));
+        let wants_tick = handler.wants_tick();
+        let tick_interval = handler.tick_interval();
+
+        let worker_rx = rx.clone();
+        let worker_tick_pending = tick_pending.clone();
+        let worker_animating = animating.clone();
+        let handle = thread::Builder::new()
+            .name("kairoi-worker".to_string())
+            .spawn(move || {
+                Self::worker_loop(handler, worker_rx, error_tx, worker_tick_pending, worker_animating)
+            })
+            .expect("failed to spawn kairoi worker thread");
+
+        Self {
+            tx,
+            rx,
+            errors,
+            tick_pending,
+            animating,
+            overflow,
+            wants_tick,
+            tick_interval,
+            handle: RefCell::new(Some(handle)),
+        }
+    }
+
+    fn worker_loop(
+        handler: Box<dyn Handler>,
+        rx: Receiver<Message>,
+        errors: Sender<SendSyncError>,
+        tick_pending: Arc<AtomicBool>,
+        animating: Arc<AtomicBool>,
+    ) {
+        mark_handler_thread();
+
+        if let Err(e) = handler.on_start() {
+            let _ = errors.send(e);
+        }
+
+        while let Ok(message) = rx.recv() {
+            let result = match message {
+                Message::Event(event) => {
+                    if !handler.interest(&event.metadata()) {
+                        continue;
+                    }
+                    handler.handle(&event)
+                }
+                Message::Tick(root) => {
+                    tick_pending.store(false, Ordering::Release);
+                    handler.tick(&root)
+                }
+                Message::Flush(ack) => {
+                    let _ = ack.send(handler.flush());
+                    continue;
+                }
+                Message::Suspend(ack) => {
+                    let _ = ack.send(handler.suspend());
+                    continue;
+                }
+                Message::Shutdown(ack) => {
+                    let _ = ack.send(handler.flush().and_then(|_| handler.shutdown()));
+                    return;
+                }
+            };
+
+            animating.store(handler.animating(), Ordering::Release);
+            if let Err(e) = result {
+                let _ = errors.send(e);
+            }
+        }
+    }
+
+    fn take_error(&self) -> Result<(), SendSyncError> {
+        match self.errors.try_recv() {
+            Ok(e) => Err(e),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn enqueue(&self, mut message: Message) -> Result<(), SendSyncError> {
+        loop {
+            match self.tx.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(_)) => return Err("Worker thread has stopped".into()),
+                Err(TrySendError::Full(m)) => match self.overflow {
+                    Overflow::Block => {
+                        return self
+                            .tx
+                            .send(m)
+                            .map_err(|_| "Worker thread has stopped".into());
+                    }
+                    Overflow::DropNewest => return Ok(()),
+                    Overflow::DropOldest => {
+                        if let Ok(Message::Tick(_)) = self.rx.try_recv() {
+                            self.tick_pending.store(false, Ordering::Release);
+                        }
+                        message = m;
+                    }
+                },
+            }
+        }
+    }
+
+    fn request(
+        &self,
+        make: fn(Sender<Result<(), SendSyncError>>) -> Message,
+    ) -> Result<(), SendSyncError> {
+        let (ack, ack_rx) = bounded(1);
+        self.tx
+            .send(make(ack))
+            .map_err(|_| SendSyncError::from("Worker thread has stopped"))?;
+
+        ack_rx
+            .recv()
+            .unwrap_or_else(|_| Err("Worker thread has stopped".into()))
+    }
+}
+
+impl Handler for IsolatedHandler {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        self.take_error()?;
+        self.enqueue(Message::Event(event.clone()))
+    }
+
+    fn tick(&self, root: &SpanRef) -> Result<(), SendSyncError> {
+        self.take_error()?;
+
+        // frames are disposable; never queue more than one or wait for room
+        if !self.tick_pending.swap(true, Ordering::AcqRel)
+            && self.tx.try_send(Message::Tick(root.clone())).is_err()
+        {
+            self.tick_pending.store(false, Ordering::Release);
+        }
+
+        Ok(())
+    }
+
+    fn wants_tick(&self) -> bool {
+        self.wants_tick
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.tick_interval
+    }
+
+    fn flush(&self) -> Result<(), SendSyncError> {
+        self.request(Message::Flush)?;
+        self.take_error()
+    }
+
+    // waits for the worker, so nothing is drawn over once this returns
+    fn suspend(&self) -> Result<(), SendSyncError> {
+        self.request(Message::Suspend)
+    }
+
+    fn animating(&self) -> bool {
+        self.animating.load(Ordering::Acquire)
+    }
+
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        let result = self.request(Message::Shutdown);
+        if let Some(handle) = self.handle.borrow_mut().take() {
+            let _ = handle.join();
+        }
+        result?;
+        self.take_error()
+    }
+}
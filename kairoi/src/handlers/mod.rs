@@ -1,3 +1,15 @@
+mod collapse;
 mod console_handler;
+mod isolated_handler;
+#[cfg(feature = "tui")]
+mod tui_backend;
+#[cfg(feature = "tui")]
+mod tui_handler;
 
+pub use collapse::SpanPriority;
 pub use console_handler::*;
+pub use isolated_handler::*;
+#[cfg(feature = "tui")]
+pub use tui_backend::*;
+#[cfg(feature = "tui")]
+pub use tui_handler::*;
@@ -0,0 +1,221 @@
+use crate::terminal::strip_escapes;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, event, execute, queue, style, terminal};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{stdout, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Tab,
+    Enter,
+    Esc,
+    Backspace,
+    Char(char),
+    /// Ctrl+C, which raw mode keeps from raising `SIGINT`.
+    Interrupt,
+}
+
+/// Screen the TUI handler draws on.
+pub trait Backend: Send {
+    /// Columns and rows.
+    fn size(&self) -> io::Result<(usize, usize)>;
+
+    /// Takes over the screen, e.g. by switching to the alternate screen.
+    fn enter(&mut self) -> io::Result<()>;
+
+    /// Gives the screen back as it was before `enter`.
+    fn leave(&mut self) -> io::Result<()>;
+
+    /// `lines` fill the screen from the top; each is as wide as the screen.
+    fn draw(&mut self, lines: &[String]) -> io::Result<()>;
+
+    /// A key pressed since the last call, without waiting for one.
+    fn poll_key(&mut self) -> io::Result<Option<Key>>;
+
+    /// Writes `text` to the normal screen, after `leave`.
+    fn print(&mut self, text: &str) -> io::Result<()>;
+}
+
+/// Alternate screen of the terminal attached to stdout, in raw mode.
+#[derive(Default)]
+pub struct CrosstermBackend {
+    // lines on screen, so only changed ones are written
+    drawn: Vec<String>,
+}
+
+impl CrosstermBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn size(&self) -> io::Result<(usize, usize)> {
+        let (w, h) = terminal::size()?;
+        Ok((w as usize, h as usize))
+    }
+
+    fn enter(&mut self) -> io::Result<()> {
+        self.drawn.clear();
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let mut out = stdout().lock();
+        if self.drawn.len() != lines.len() {
+            self.drawn.clear();
+            queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        }
+
+        for (row, line) in lines.iter().enumerate() {
+            if self.drawn.get(row) == Some(line) {
+                continue;
+            }
+            queue!(out, cursor::MoveTo(0, row as u16), style::Print(line))?;
+        }
+        out.flush()?;
+
+        self.drawn = lines.to_vec();
+        Ok(())
+    }
+
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+
+            let key = match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Interrupt,
+                KeyCode::Up => Key::Up,
+                KeyCode::Down => Key::Down,
+                KeyCode::PageUp => Key::PageUp,
+                KeyCode::PageDown => Key::PageDown,
+                KeyCode::Home => Key::Home,
+                KeyCode::End => Key::End,
+                KeyCode::Tab => Key::Tab,
+                KeyCode::Enter => Key::Enter,
+                KeyCode::Esc => Key::Esc,
+                KeyCode::Backspace => Key::Backspace,
+                KeyCode::Char(c) => Key::Char(c),
+                _ => continue,
+            };
+            return Ok(Some(key));
+        }
+
+        Ok(None)
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        let mut out = stdout().lock();
+        out.write_all(text.as_bytes())?;
+        out.flush()
+    }
+}
+
+struct Screen {
+    size: (usize, usize),
+    lines: Vec<String>,
+    keys: VecDeque<Key>,
+    active: bool,
+    printed: String,
+}
+
+/// In-memory screen; clones share it, so one can be handed to the handler
+/// while another feeds keys and inspects what was drawn.
+#[derive(Clone)]
+pub struct VirtualBackend {
+    screen: Arc<Mutex<Screen>>,
+}
+
+impl VirtualBackend {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            screen: Arc::new(Mutex::new(Screen {
+                size: (width, height),
+                lines: Vec::new(),
+                keys: VecDeque::new(),
+                active: false,
+                printed: String::new(),
+            })),
+        }
+    }
+
+    pub fn resize(&self, width: usize, height: usize) {
+        self.screen.lock().size = (width, height);
+    }
+
+    /// Delivered on the next tick.
+    pub fn press(&self, key: Key) {
+        self.screen.lock().keys.push_back(key);
+    }
+
+    /// What was drawn last, with escape sequences.
+    pub fn lines(&self) -> Vec<String> {
+        self.screen.lock().lines.clone()
+    }
+
+    /// What was drawn last, as plain text.
+    pub fn text(&self) -> Vec<String> {
+        self.lines().iter().map(|line| strip_escapes(line)).collect()
+    }
+
+    /// What was written to the normal screen.
+    pub fn printed(&self) -> String {
+        self.screen.lock().printed.clone()
+    }
+
+    /// Whether the handler holds the screen.
+    pub fn active(&self) -> bool {
+        self.screen.lock().active
+    }
+}
+
+impl Backend for VirtualBackend {
+    fn size(&self) -> io::Result<(usize, usize)> {
+        Ok(self.screen.lock().size)
+    }
+
+    fn enter(&mut self) -> io::Result<()> {
+        self.screen.lock().active = true;
+        Ok(())
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        self.screen.lock().active = false;
+        Ok(())
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        self.screen.lock().lines = lines.to_vec();
+        Ok(())
+    }
+
+    fn poll_key(&mut self) -> io::Result<Option<Key>> {
+        Ok(self.screen.lock().keys.pop_front())
+    }
+
+    fn print(&mut self, text: &str) -> io::Result<()> {
+        self.screen.lock().printed.push_str(text);
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+use crate::error::SendSyncError;
+use crate::format::{format_duration, DefaultFormatter, FormatterSet, Writer};
+use crate::handlers::tui_backend::{Backend, CrosstermBackend, Key};
+use crate::terminal::{fit, strip_escapes};
+use crate::{Event, GlobalHandlerBuilder, Handler, Id, Log, SpanRef};
+use chrono::{DateTime, Local};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+pub trait AddTuiHandler<T> {
+    fn tui_handler(self) -> T;
+}
+
+impl AddTuiHandler<GlobalHandlerBuilder> for GlobalHandlerBuilder {
+    fn tui_handler(self) -> Self {
+        self.handler(Box::new(TuiHandler::new()))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Pane {
+    Logs,
+    Spans,
+}
+
+// formatted once, when the log comes in
+struct Entry {
+    log: Arc<Log>,
+    lines: Vec<String>,
+}
+
+struct State {
+    logs: VecDeque<Entry>,
+    focus: Pane,
+    // lines up from the bottom of the log pane; 0 follows new logs
+    scroll: usize,
+    selected: Option<Id>,
+    filter: String,
+    editing: bool,
+    entered: bool,
+    // something changed since the last draw
+    dirty: bool,
+    size: (usize, usize),
+}
+
+// rows of each pane, for paging
+struct Layout {
+    width: usize,
+    height: usize,
+    logs: usize,
+    tree: usize,
+    detail: usize,
+}
+
+impl Layout {
+    fn new(width: usize, height: usize) -> Self {
+        // a status line at the top, a key help line at the bottom and a title per pane
+        let body = height.saturating_sub(2);
+        let tree = body.saturating_sub(2) / 2;
+        Self {
+            width,
+            height,
+            logs: body.saturating_sub(1),
+            tree,
+            detail: body.saturating_sub(2 + tree),
+        }
+    }
+
+    fn left(&self) -> usize {
+        self.width * 3 / 5
+    }
+
+    fn right(&self) -> usize {
+        self.width.saturating_sub(self.left() + 1)
+    }
+}
+
+/// Full-screen dashboard on the alternate screen: logs on the left, the span tree and
+/// details of the selected span on the right.
+///
+/// `Tab` switches between logs and spans, arrows and `PgUp`/`PgDn`/`Home`/`End` scroll
+/// or select, `/` filters logs and `Esc` clears the filter.
+/// `Ctrl+C` gives the screen back and raises `SIGINT`, as it would outside of raw mode.
+/// Logs kept in memory are printed to the normal screen on shutdown.
+pub struct TuiHandler {
+    backend: RefCell<Box<dyn Backend>>,
+    formatter: Box<dyn FormatterSet>,
+    state: RefCell<State>,
+}
+
+impl TuiHandler {
+    const LOG_CAPACITY: usize = 10_000;
+    const MIN_SIZE: (usize, usize) = (40, 8);
+
+    pub fn new() -> Self {
+        Self::with_backend(Box::new(CrosstermBackend::new()))
+    }
+
+    pub fn with_backend(backend: Box<dyn Backend>) -> Self {
+        Self {
+            backend: RefCell::new(backend),
+            formatter: Box::new(DefaultFormatter::new()),
+            state: RefCell::new(State {
+                logs: VecDeque::new(),
+                focus: Pane::Logs,
+                scroll: 0,
+                selected: None,
+                filter: String::new(),
+                editing: false,
+                entered: false,
+                dirty: true,
+                size: (0, 0),
+            }),
+        }
+    }
+}
+
+impl Default for TuiHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn reverse(line: &str, width: usize) -> String {
+    format!("\x1b[7m{}\x1b[0m", fit(&strip_escapes(line), width))
+}
+
+impl TuiHandler {
+    fn matches(filter: &str, log: &Log) -> bool {
+        let filter = filter.to_lowercase();
+        filter.is_empty()
+            || log.message().to_lowercase().contains(&filter)
+            || log.span().value().name().to_lowercase().contains(&filter)
+    }
+
+    fn format_log(&self, log: &Log) -> Result<Vec<String>, SendSyncError> {
+        let mut line = String::new();
+        self.formatter.format(&mut Writer::Fmt(&mut line), log)?;
+        Ok(line.lines().map(str::to_owned).collect())
+    }
+
+    fn press(&self, state: &mut State, key: Key, layout: &Layout, spans: &[SpanRef]) {
+        if state.editing {
+            match key {
+                Key::Char(c) => state.filter.push(c),
+                Key::Backspace => {
+                    state.filter.pop();
+                }
+                Key::Enter => state.editing = false,
+                Key::Esc => {
+                    state.editing = false;
+                    state.filter.clear();
+                }
+                _ => {}
+            }
+            state.scroll = 0;
+            return;
+        }
+
+        match key {
+            Key::Tab => {
+                state.focus = match state.focus {
+                    Pane::Logs => Pane::Spans,
+                    Pane::Spans => Pane::Logs,
+                }
+            }
+            Key::Char('/') => state.editing = true,
+            Key::Esc => {
+                state.filter.clear();
+                state.scroll = 0;
+            }
+            _ => match state.focus {
+                Pane::Logs => {
+                    state.scroll = match key {
+                        Key::Up => state.scroll + 1,
+                        Key::Down => state.scroll.saturating_sub(1),
+                        Key::PageUp => state.scroll + layout.logs,
+                        Key::PageDown => state.scroll.saturating_sub(layout.logs),
+                        Key::Home => usize::MAX,
+                        Key::End => 0,
+                        _ => state.scroll,
+                    }
+                }
+                Pane::Spans => {
+                    if spans.is_empty() {
+                        return;
+                    }
+
+                    let current = state
+                        .selected
+                        .and_then(|id| spans.iter().position(|span| span.id() == id))
+                        .unwrap_or(0);
+                    let last = spans.len() - 1;
+                    let next = match key {
+                        Key::Up => current.saturating_sub(1),
+                        Key::Down => (current + 1).min(last),
+                        Key::PageUp => current.saturating_sub(layout.tree),
+                        Key::PageDown => (current + layout.tree).min(last),
+                        Key::Home => 0,
+                        Key::End => last,
+                        _ => current,
+                    };
+                    state.selected = Some(spans[next].id());
+                }
+            },
+        }
+    }
+
+    fn render_logs(&self, state: &mut State, layout: &Layout) -> Vec<String> {
+        let rows = layout.logs;
+
+        // newest first, and only as far back as the view reaches
+        let mut lines: Vec<&String> = Vec::new();
+        for entry in state.logs.iter().rev() {
+            if lines.len() >= state.scroll.saturating_add(rows) {
+                break;
+            }
+            if Self::matches(&state.filter, &entry.log) {
+                lines.extend(entry.lines.iter().rev());
+            }
+        }
+
+        state.scroll = state.scroll.min(lines.len().saturating_sub(rows));
+        let end = lines.len().min(state.scroll + rows);
+
+        let mut pane: Vec<String> = lines[state.scroll..end].iter().rev().map(|line| line.to_string()).collect();
+        pane.resize(rows, String::new());
+        pane
+    }
+
+    fn render_tree(
+        &self,
+        state: &State,
+        layout: &Layout,
+        spans: &[SpanRef],
+        selected: Option<usize>,
+    ) -> Result<Vec<String>, SendSyncError> {
+        let rows = layout.tree;
+        let start = match selected {
+            Some(i) if i >= rows => i + 1 - rows,
+            _ => 0,
+        };
+
+        let mut pane = Vec::with_capacity(rows);
+        if spans.is_empty() {
+            pane.push("no running spans".to_string());
+        }
+        for (i, span) in spans.iter().enumerate().skip(start).take(rows) {
+            let mut line = String::new();
+            self.formatter.format(&mut Writer::Fmt(&mut line), span)?;
+            let line = line.lines().next().unwrap_or_default().to_string();
+
+            if Some(i) == selected && state.focus == Pane::Spans {
+                pane.push(reverse(&line, layout.right()));
+            } else {
+                pane.push(line);
+            }
+        }
+        pane.resize(rows, String::new());
+        Ok(pane)
+    }
+
+    fn render_detail(
+        &self,
+        state: &State,
+        layout: &Layout,
+        span: Option<&SpanRef>,
+    ) -> Result<Vec<String>, SendSyncError> {
+        let rows = layout.detail;
+        let mut pane = Vec::with_capacity(rows);
+
+        if let Some(span) = span {
+            let value = span.value();
+            let started: DateTime<Local> = value.timestamp().into();
+
+            pane.push(span.path().join(" > "));
+            pane.push(format!(
+                "{:?} · {} · started {}",
+                value.status(),
+                format_duration(value.elapsed()),
+                started.format("%H:%M:%S%.3f")
+            ));
+            if let Some(p) = value.progress() {
+                let percent = if p.total() > 0 { p.progress() * 100 / p.total() } else { 0 };
+                pane.push(format!("progress {}/{} ({percent}%)", p.progress(), p.total()));
+            }
+            pane.push(format!("{:?} · {} children", span.id(), span.child_count()));
+            pane.push("logs".to_string());
+
+            let tail = rows.saturating_sub(pane.len());
+            let mut logs = Vec::new();
+            for log in state.logs.iter().rev().map(|entry| &entry.log) {
+                if logs.len() >= tail {
+                    break;
+                }
+                if log.span().id() == span.id() {
+                    logs.push(format!("  {:>5} {}", log.level(), log.message()));
+                }
+            }
+            pane.extend(logs.into_iter().rev());
+        }
+
+        pane.truncate(rows);
+        pane.resize(rows, String::new());
+        Ok(pane)
+    }
+
+    fn render(
+        &self,
+        state: &mut State,
+        layout: &Layout,
+        spans: &[SpanRef],
+    ) -> Result<Vec<String>, SendSyncError> {
+        if layout.width < Self::MIN_SIZE.0 || layout.height < Self::MIN_SIZE.1 {
+            let mut lines = vec![fit("terminal too small", layout.width)];
+            lines.resize(layout.height, fit("", layout.width));
+            return Ok(lines);
+        }
+
+        let selected = match state.selected.and_then(|id| spans.iter().position(|s| s.id() == id)) {
+            Some(i) => Some(i),
+            None if spans.is_empty() => None,
+            None => Some(0),
+        };
+        state.selected = selected.map(|i| spans[i].id());
+
+        let (left, right) = (layout.left(), layout.right());
+        let focus = state.focus;
+        let title = |name: &str, pane: Option<Pane>| {
+            if pane.is_some_and(|pane| pane == focus) {
+                format!("\x1b[1m{name}\x1b[0m")
+            } else {
+                name.to_string()
+            }
+        };
+
+        let mut status = format!(" kairoi │ {} spans │ {} logs", spans.len(), state.logs.len());
+        if state.editing || !state.filter.is_empty() {
+            status.push_str(&format!(" │ filter: {}", state.filter));
+            if state.editing {
+                status.push('▏');
+            }
+        }
+
+        let mut lines = Vec::with_capacity(layout.height);
+        lines.push(reverse(&status, layout.width));
+
+        let mut left_pane = vec![title(" Logs", Some(Pane::Logs))];
+        left_pane.extend(self.render_logs(state, layout));
+
+        let mut right_pane = vec![title(" Spans", Some(Pane::Spans))];
+        right_pane.extend(self.render_tree(state, layout, spans, selected)?);
+        right_pane.push(title(" Detail", None));
+        right_pane.extend(self.render_detail(state, layout, selected.map(|i| &spans[i]))?);
+
+        for (l, r) in left_pane.iter().zip(&right_pane) {
+            lines.push(format!("{}│{}", fit(l, left), fit(r, right)));
+        }
+
+        let help = " Tab pane │ ↑↓ PgUp PgDn Home End move │ / filter │ Esc clear │ Ctrl+C quit";
+        lines.push(reverse(help, layout.width));
+        lines.resize(layout.height, fit("", layout.width));
+
+        Ok(lines)
+    }
+
+    fn leave(&self) -> Result<(), SendSyncError> {
+        let mut state = self.state.borrow_mut();
+        if state.entered {
+            state.entered = false;
+            self.backend.borrow_mut().leave()?;
+        }
+        Ok(())
+    }
+
+    // raw mode keeps the terminal from raising it; the screen is taken again on the next tick
+    // if the process handles it and goes on
+    fn interrupt(&self) -> Result<(), SendSyncError> {
+        self.leave()?;
+        unsafe {
+            libc::raise(libc::SIGINT);
+        }
+        Ok(())
+    }
+}
+
+impl Handler for TuiHandler {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        let mut state = self.state.borrow_mut();
+        state.dirty = true;
+
+        if let Event::Log(log) = event {
+            let lines = self.format_log(log)?;
+            // keep the view in place while scrolled up
+            if state.scroll > 0 && Self::matches(&state.filter, log) {
+                state.scroll += lines.len();
+            }
+
+            state.logs.push_back(Entry {
+                log: log.clone(),
+                lines,
+            });
+            if state.logs.len() > Self::LOG_CAPACITY {
+                state.logs.pop_front();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tick(&self, root: &SpanRef) -> Result<(), SendSyncError> {
+        let mut state = self.state.borrow_mut();
+        if !state.entered {
+            self.backend.borrow_mut().enter()?;
+            state.entered = true;
+            state.dirty = true;
+        }
+
+        let spans = root.find_all(|node| node.depth() > 0);
+        let size = self.backend.borrow().size()?;
+        let layout = Layout::new(size.0, size.1);
+
+        loop {
+            // the backend must not stay borrowed while the key is handled
+            let key = self.backend.borrow_mut().poll_key()?;
+            let Some(key) = key else {
+                break;
+            };
+            if key == Key::Interrupt {
+                drop(state);
+                return self.interrupt();
+            }
+            self.press(&mut state, key, &layout, &spans);
+            state.dirty = true;
+        }
+
+        // running spans show how long they have been running
+        if !state.dirty && state.size == size && spans.is_empty() {
+            return Ok(());
+        }
+        state.dirty = false;
+        state.size = size;
+
+        let lines = self.render(&mut state, &layout, &spans)?;
+        self.backend.borrow_mut().draw(&lines)?;
+
+        Ok(())
+    }
+
+    fn suspend(&self) -> Result<(), SendSyncError> {
+        self.leave()
+    }
+
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        self.leave()?;
+
+        // the alternate screen is gone; leave the logs in the scrollback
+        let mut out = String::new();
+        for line in self.state.borrow().logs.iter().flat_map(|entry| &entry.lines) {
+            out.push_str(line);
+            out.push('\n');
+        }
+        self.backend.borrow_mut().print(&out)?;
+
+        Ok(())
+    }
+
+    // keys are read on ticks; nothing is drawn unless something changed
+    fn animating(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::SendSyncError;
+use crate::schema;
+use crate::{Formatter, FormatterSet, Id, Log, Progress, SpanRef, Write, Writer};
+use std::fmt::Write as _;
+use std::time::SystemTime;
+
+fn string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn timestamp(out: &mut String, t: SystemTime) {
+    string(out, &schema::timestamp(t));
+}
+
+// `span_id`, `parent_id`, `span_path` and `progress`
+fn span(out: &mut String, span: &SpanRef) {
+    let id = schema::span_id(span);
+    let progress = id.and(span.value().progress());
+
+    let _ = write!(out, ",\"span_id\":{}", number(id));
+    let _ = write!(out, ",\"parent_id\":{}", number(schema::parent_id(span)));
+
+    out.push_str(",\"span_path\":[");
+    for (i, name) in span.path().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        string(out, name);
+    }
+    out.push(']');
+
+    out.push_str(",\"progress\":");
+    match progress {
+        Some(p) => progress_object(out, p),
+        None => out.push_str("null"),
+    }
+}
+
+fn number(id: Option<Id>) -> String {
+    id.map_or("null".to_string(), |id| id.get().to_string())
+}
+
+fn progress_object(out: &mut String, p: Progress) {
+    let _ = write!(out, "{{\"total\":{},\"progress\":{}}}", p.total(), p.progress());
+}
+
+/// Writes every log and span as one JSON object per line, for log shippers that take NDJSON.
+///
+/// Logs look like
+/// `{"timestamp":"…","kind":"log","level":"info","message":"…","fields":{"key":"value"},"span_id":3,"parent_id":1,"span_path":["a","b"],"progress":null}`;
+/// spans have `"kind":"span"`, `name`, `status` and `elapsed` in seconds instead of
+/// `level`, `message` and `fields`, and `timestamp` is when they started.
+/// Outside of any span, `span_id` and `parent_id` are `null`.
+///
+/// See [`ConsoleLayout::Logs`](crate::ConsoleLayout::Logs) for writing nothing else to the console.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct JsonFormatter;
+
+impl JsonFormatter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Formatter<Log> for JsonFormatter {
+    fn format(&self, to: &mut Writer, log: &Log) -> Result<(), SendSyncError> {
+        let mut out = String::from("{\"timestamp\":");
+        timestamp(&mut out, log.timestamp());
+        out.push_str(",\"kind\":\"log\",\"level\":");
+        string(&mut out, &log.level().as_str().to_lowercase());
+        out.push_str(",\"message\":");
+        string(&mut out, log.message());
+
+        out.push_str(",\"fields\":{");
+        for (i, (key, value)) in log.fields().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            string(&mut out, key);
+            out.push(':');
+            string(&mut out, value);
+        }
+        out.push('}');
+
+        span(&mut out, log.span());
+        out.push('}');
+
+        writeln!(to, "{out}")
+    }
+}
+
+impl Formatter<SpanRef> for JsonFormatter {
+    fn format(&self, to: &mut Writer, value: &SpanRef) -> Result<(), SendSyncError> {
+        let data = value.value();
+
+        let mut out = String::from("{\"timestamp\":");
+        timestamp(&mut out, data.timestamp());
+        out.push_str(",\"kind\":\"span\",\"name\":");
+        string(&mut out, data.name());
+
+        let _ = write!(out, ",\"status\":\"{}\"", data.status().as_str());
+        let _ = write!(out, ",\"elapsed\":{:.3}", data.elapsed().as_secs_f64());
+
+        span(&mut out, value);
+        out.push('}');
+
+        writeln!(to, "{out}")
+    }
+}
+
+impl FormatterSet for JsonFormatter {
+    fn format_completion(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        self.format(to, span)
+    }
+}
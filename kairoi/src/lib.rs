@@ -1,22 +1,44 @@
-#![feature(async_fn_traits)]
 #![feature(unboxed_closures)]
 #![feature(stmt_expr_attributes)]
 
 mod channel;
+mod control;
+mod dispatcher;
+mod entry;
 mod error;
 mod event;
 mod format;
 mod handler;
 mod handlers;
+mod json;
+mod logfmt;
 mod macros;
 mod node;
+mod panic;
+mod pattern;
+mod policy;
+mod schema;
 mod span;
+mod terminal;
+mod theme;
+pub mod testing;
 
+pub use control::*;
+pub use dispatcher::*;
+pub use entry::__main;
+pub use error::SendSyncError;
 pub use event::*;
 pub use format::*;
 pub use handler::*;
 pub use handlers::*;
+pub use json::*;
+pub use logfmt::*;
 pub use node::*;
+pub use panic::*;
+pub use pattern::*;
+pub use policy::*;
 pub use span::*;
+pub use terminal::*;
+pub use theme::*;
 
-pub use kairoi_macros::instrument;
+pub use kairoi_macros::{instrument, main};
@@ -0,0 +1,125 @@
+use crate::error::SendSyncError;
+use crate::schema;
+use crate::{Formatter, FormatterSet, Log, SpanRef, Write, Writer};
+
+// keys of a log line written by the formatter itself
+const RESERVED: [&str; 6] = ["ts", "level", "span", "span_id", "parent_id", "msg"];
+
+// keys are bare words, so anything that would end one is replaced
+fn key(key: &str) -> String {
+    if key.is_empty() {
+        return "_".to_string();
+    }
+    key.chars()
+        .map(|c| {
+            if c == '=' || c == '"' || c.is_whitespace() || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+// quoted only when it has to be
+fn value(out: &mut String, value: &str) {
+    let bare = !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c == '=' || c == '"' || c == '\\' || c.is_whitespace() || c.is_control());
+    if bare {
+        out.push_str(value);
+        return;
+    }
+
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn pair(out: &mut String, k: &str, v: &str) {
+    out.push(' ');
+    out.push_str(&key(k));
+    out.push('=');
+    value(out, v);
+}
+
+fn span(out: &mut String, span: &SpanRef) {
+    pair(out, "span", &span.path().join("/"));
+    pair(out, "span_id", &span.id().get().to_string());
+    if let Some(parent) = schema::parent_id(span) {
+        pair(out, "parent_id", &parent.get().to_string());
+    }
+}
+
+/// Writes every log and span as one logfmt line, like
+/// `ts=2025-01-01T12:00:00.000000000Z level=info span=fetch/parse span_id=3 parent_id=1 msg="parsed rows" rows=42`.
+///
+/// Spans are written as `ts=… span=fetch/parse span_id=3 parent_id=1 status=completed elapsed=1.250s progress=3/10`,
+/// with `ts` being when they started. Logs outside of any span have no `span`, `span_id` or
+/// `parent_id`, and top-level spans have no `parent_id`. Values are quoted if they are empty or hold spaces,
+/// `=`, quotes or control characters; in keys, these are replaced with `_`.
+/// Fields named like the keys above are written as `field.ts=…` and so on.
+///
+/// See [`ConsoleLayout::Logs`](crate::ConsoleLayout::Logs) for writing nothing else to the console.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LogfmtFormatter;
+
+impl LogfmtFormatter {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Formatter<Log> for LogfmtFormatter {
+    fn format(&self, to: &mut Writer, log: &Log) -> Result<(), SendSyncError> {
+        let mut out = format!("ts={}", schema::timestamp(log.timestamp()));
+        pair(&mut out, "level", &log.level().as_str().to_lowercase());
+        if log.span().depth() > 0 {
+            span(&mut out, log.span());
+        }
+        pair(&mut out, "msg", log.message());
+        for (k, v) in log.fields() {
+            let k = key(k);
+            if RESERVED.contains(&k.as_str()) {
+                pair(&mut out, &format!("field.{k}"), v);
+            } else {
+                pair(&mut out, &k, v);
+            }
+        }
+
+        writeln!(to, "{out}")
+    }
+}
+
+impl Formatter<SpanRef> for LogfmtFormatter {
+    fn format(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        let data = span.value();
+
+        let mut out = format!("ts={}", schema::timestamp(data.timestamp()));
+        self::span(&mut out, span);
+        pair(&mut out, "status", data.status().as_str());
+        pair(&mut out, "elapsed", &format!("{:.3}s", data.elapsed().as_secs_f64()));
+        if let Some(p) = data.progress() {
+            pair(&mut out, "progress", &format!("{}/{}", p.progress(), p.total()));
+        }
+
+        writeln!(to, "{out}")
+    }
+}
+
+impl FormatterSet for LogfmtFormatter {
+    fn format_completion(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        self.format(to, span)
+    }
+}
@@ -1,42 +1,117 @@
-
+/// Logs a formatted message, optionally preceded by fields:
+///
+/// ```ignore
+/// log!(Level::Info, "fetched {} rows", rows);
+/// log!(Level::Info, user = name, attempt = 3; "fetched {} rows", rows);
+/// ```
+///
+/// Field values are recorded with their `Display` implementation.
 #[macro_export]
 macro_rules! log {
+    ($level:expr, $($key:ident = $value:expr),+ ; $($arg:expr),+) => {
+        $crate::Event::log_with_fields(
+            $level,
+            format!($($arg),+),
+            vec![$((stringify!($key).to_string(), $value.to_string())),+],
+        )
+        .submit();
+    };
     ($level:expr, $($arg:expr),+) => {
         $crate::Event::log($level, format!($($arg),+)).submit();
-    }
+    };
 }
 
 #[macro_export]
 macro_rules! error {
-    ($($arg:expr),+) => {
-        $crate::log!($crate::Level::Error, $($arg),+);
+    ($($arg:tt)+) => {
+        $crate::log!($crate::Level::Error, $($arg)+);
     }
 }
 
 #[macro_export]
 macro_rules! warn {
-    ($($arg:expr),+) => {
-        $crate::log!($crate::Level::Warn, $($arg),+);
+    ($($arg:tt)+) => {
+        $crate::log!($crate::Level::Warn, $($arg)+);
     }
 }
 
 #[macro_export]
 macro_rules! info {
-    ($($arg:expr),+) => {
-        $crate::log!($crate::Level::Info, $($arg),+);
+    ($($arg:tt)+) => {
+        $crate::log!($crate::Level::Info, $($arg)+);
     }
 }
 
 #[macro_export]
 macro_rules! debug {
-    ($($arg:expr),+) => {
-        $crate::log!($crate::Level::Debug, $($arg),+);
+    ($($arg:tt)+) => {
+        $crate::log!($crate::Level::Debug, $($arg)+);
     }
 }
 
 #[macro_export]
 macro_rules! trace {
-    ($($arg:expr),+) => {
-        $crate::log!($crate::Level::Trace, $($arg),+);
+    ($($arg:tt)+) => {
+        $crate::log!($crate::Level::Trace, $($arg)+);
     }
 }
+
+/// `std::println!` that is written above the live output of handlers instead of through it.
+///
+/// Logs are flushed first, so they keep their order with the printed line.
+#[macro_export]
+macro_rules! suspended_println {
+    ($($arg:tt)*) => {
+        $crate::suspend(|| ::std::println!($($arg)*))
+    };
+}
+
+/// Asserts that a log of the given level was captured.
+///
+/// ```ignore
+/// assert_logged!(Level::Warn, contains "retry");
+/// assert_logged!(capture, Level::Info, "exact message");
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+    ($level:expr, contains $needle:expr) => {
+        $crate::testing::__assert_logged(&$crate::testing::__current_or_panic(), $level, $needle, false)
+    };
+    ($level:expr, $message:literal) => {
+        $crate::testing::__assert_logged(&$crate::testing::__current_or_panic(), $level, $message, true)
+    };
+    ($capture:expr, $level:expr, contains $needle:expr) => {
+        $crate::testing::__assert_logged(&$capture, $level, $needle, false)
+    };
+    ($capture:expr, $level:expr, $message:expr) => {
+        $crate::testing::__assert_logged(&$capture, $level, $message, true)
+    };
+}
+
+/// Asserts that a span was captured, given the names of its innermost ancestors and itself.
+///
+/// ```ignore
+/// assert_span!("fetch" > "parse", completed);
+/// assert_span!(capture, "fetch", running);
+/// ```
+#[macro_export]
+macro_rules! assert_span {
+    ($($name:literal)>+) => {
+        $crate::testing::__assert_span(&$crate::testing::__current_or_panic(), &[$($name),+], None)
+    };
+    ($($name:literal)>+, completed) => {
+        $crate::testing::__assert_span(&$crate::testing::__current_or_panic(), &[$($name),+], Some(true))
+    };
+    ($($name:literal)>+, running) => {
+        $crate::testing::__assert_span(&$crate::testing::__current_or_panic(), &[$($name),+], Some(false))
+    };
+    ($capture:expr, $($name:literal)>+) => {
+        $crate::testing::__assert_span(&$capture, &[$($name),+], None)
+    };
+    ($capture:expr, $($name:literal)>+, completed) => {
+        $crate::testing::__assert_span(&$capture, &[$($name),+], Some(true))
+    };
+    ($capture:expr, $($name:literal)>+, running) => {
+        $crate::testing::__assert_span(&$capture, &[$($name),+], Some(false))
+    };
+}
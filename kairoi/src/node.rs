@@ -1,11 +1,16 @@
-use dashmap::DashMap;
-use std::ops::{Deref, DerefMut};
+use std::ops::DerefMut;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::AcqRel;
 use std::sync::{Arc, Weak};
 use parking_lot::Mutex;
 
+/// Unique among the nodes of a process.
+///
+/// Ids read back with the `serde` feature are the ones they were written with, possibly
+/// by another process, so they may equal the id of a live node; compare them only with
+/// ids read from the same output.
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Id(usize);
 
 impl Id {
@@ -13,6 +18,10 @@ impl Id {
         static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
         Self(ID_COUNTER.fetch_add(1, AcqRel))
     }
+
+    pub fn get(&self) -> usize {
+        self.0
+    }
 }
 
 pub struct Node<T: Send + Sync> {
@@ -20,7 +29,8 @@ pub struct Node<T: Send + Sync> {
     value: Mutex<Arc<T>>,
     depth: usize,
     parent: Weak<Node<T>>,
-    children: DashMap<Id, Arc<Node<T>>>,
+    // in insertion order
+    children: Mutex<Vec<Arc<Node<T>>>>,
 }
 
 impl<T: Send + Sync> Node<T> {
@@ -30,11 +40,16 @@ impl<T: Send + Sync> Node<T> {
             value: Mutex::new(Arc::new(value)),
             depth: 0,
             parent: Weak::new(),
-            children: DashMap::new(),
+            children: Mutex::new(Vec::new()),
         }
     }
 
 
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_id(id: Id, value: T) -> Self {
+        Self { id, ..Self::new(value) }
+    }
+
     pub fn id(&self) -> Id {
         self.id
     }
@@ -51,18 +66,28 @@ impl<T: Send + Sync> Node<T> {
         self.parent.upgrade()
     }
 
+    /// Snapshot of the children in the order they were added.
     pub fn children(&self) -> impl Iterator<Item = Arc<Self>> {
-        self.children.iter().map(|v| v.deref().clone())
+        self.children.lock().clone().into_iter()
     }
 
+    /// Like `children().next().is_some()`, without taking a snapshot.
+    pub fn has_children(&self) -> bool {
+        !self.children.lock().is_empty()
+    }
 
-    pub fn find<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: F) -> Option<Arc<Self>> {
+    pub fn child_count(&self) -> usize {
+        self.children.lock().len()
+    }
+
+
+    fn find_by<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: &F) -> Option<Arc<Self>> {
         if f(self) {
             return Some(self.clone());
         }
 
-        for item in self.children.iter() {
-            if let Some(found) = item.find(&f) {
+        for item in self.children() {
+            if let Some(found) = item.find_by(f) {
                 return Some(found);
             }
         }
@@ -70,19 +95,23 @@ impl<T: Send + Sync> Node<T> {
         None
     }
 
-    fn find_all_into<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: F, v: &mut Vec<Arc<Self>>) {
+    pub fn find<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: F) -> Option<Arc<Self>> {
+        self.find_by(&f)
+    }
+
+    fn find_all_into<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: &F, v: &mut Vec<Arc<Self>>) {
         if f(self) {
             v.push(self.clone());
         }
 
-        for item in self.children.iter() {
-            item.find_all_into(&f, v);
+        for item in self.children() {
+            item.find_all_into(f, v);
         }
     }
 
     pub fn find_all<F: Fn(&Self) -> bool>(self: &Arc<Self>, f: F) -> Vec<Arc<Self>> {
         let mut v: Vec<Arc<Self>> = Vec::new();
-        self.find_all_into(f, &mut v);
+        self.find_all_into(&f, &mut v);
         v
     }
 
@@ -91,7 +120,7 @@ impl<T: Send + Sync> Node<T> {
         child.parent = Arc::downgrade(self);
         child.depth = self.depth + 1;
         let child_ref = Arc::new(child);
-        self.children.insert(child_ref.id, child_ref.clone());
+        self.children.lock().push(child_ref.clone());
         child_ref
     }
 
@@ -101,7 +130,7 @@ impl<T: Send + Sync> Node<T> {
 
     pub fn delete(&self) {
         if let Some(parent) = self.parent.upgrade() {
-            parent.children.remove(&self.id);
+            parent.children.lock().retain(|child| child.id != self.id);
         }
     }
 }
@@ -0,0 +1,70 @@
+use crate::control::is_suspended;
+use crate::handler::is_handler_thread;
+use crate::{Dispatcher, Event, Level, Span, Status};
+use std::panic;
+use std::panic::PanicHookInfo;
+use std::sync::Once;
+use std::time::Duration;
+
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = if let Some(s) = info.payload().downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = info.payload().downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+
+    let mut message = match info.location() {
+        Some(location) => format!("panicked at {location}: {payload}"),
+        None => format!("panicked: {payload}"),
+    };
+
+    let path = Span::current().path();
+    if !path.is_empty() {
+        message.push_str(&format!(" (in {})", path.join(" > ")));
+    }
+
+    message
+}
+
+/// Routes panics through kairoi before the previously installed hook runs.
+///
+/// The panic is logged as an `Error` with its location and the span it happened in,
+/// that span is marked as `Status::Panicked`, and all handlers are flushed and
+/// cleared from the terminal, so the message of the previous hook isn't drawn over.
+/// Panics on threads that run handlers only go to the previous hook.
+/// Installing it more than once has no effect.
+pub fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+
+    INSTALLED.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            // submitting from the logging thread would block on its own channel once it is full
+            if is_handler_thread() {
+                return prev(info);
+            }
+
+            let span = Span::current();
+            if span.depth() > 0 {
+                span.update(span.value().with_status(Status::Panicked));
+            }
+
+            Event::log(Level::Error, panic_message(info)).submit();
+
+            match Dispatcher::current().control() {
+                Some(control) => {
+                    // a suspended logging thread won't flush until the panicking thread resumes it
+                    if !is_suspended() {
+                        let _ = control.flush_within(FLUSH_TIMEOUT);
+                    }
+                    control.suspend(FLUSH_TIMEOUT, || prev(info));
+                }
+                None => prev(info),
+            }
+        }));
+    });
+}
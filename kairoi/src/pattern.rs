@@ -0,0 +1,258 @@
+use crate::error::SendSyncError;
+use crate::format::{check_time_format, format_duration};
+use crate::{Formatter, FormatterSet, Log, SpanRef, Write, Writer};
+use chrono::{DateTime, Local};
+use std::time::SystemTime;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Placeholder {
+    Time,
+    Level,
+    Message,
+    Fields,
+    Span,
+    SpanPath,
+    Name,
+    Status,
+    Progress,
+    Elapsed,
+    Indent,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "time" => Self::Time,
+            "level" => Self::Level,
+            "message" => Self::Message,
+            "fields" => Self::Fields,
+            "span" => Self::Span,
+            "span_path" => Self::SpanPath,
+            "name" => Self::Name,
+            "status" => Self::Status,
+            "progress" => Self::Progress,
+            "elapsed" => Self::Elapsed,
+            "indent" => Self::Indent,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Text(String),
+    Value {
+        placeholder: Placeholder,
+        // `chrono` format of `time`
+        format: Option<String>,
+        align: Align,
+        width: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Pattern(Vec<Piece>);
+
+impl Pattern {
+    fn parse(template: &str) -> Result<Self, SendSyncError> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => return Err(format!("unclosed `{{{inner}` in pattern").into()),
+                        }
+                    }
+
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Self::parse_value(&inner)?);
+                }
+                '}' => return Err("unmatched `}` in pattern; write `}}` for a brace".into()),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self(pieces))
+    }
+
+    fn parse_value(inner: &str) -> Result<Piece, SendSyncError> {
+        let (name, spec) = inner.split_once(':').unwrap_or((inner, ""));
+        let placeholder = Placeholder::parse(name.trim())
+            .ok_or_else(|| format!("unknown placeholder `{{{name}}}` in pattern"))?;
+
+        if placeholder == Placeholder::Time {
+            check_time_format(spec).map_err(|e| format!("{e} in pattern"))?;
+
+            let format = Some(spec).filter(|spec| !spec.is_empty()).map(str::to_string);
+            return Ok(Piece::Value {
+                placeholder,
+                format,
+                align: Align::Left,
+                width: 0,
+            });
+        }
+
+        let (align, width) = match spec.chars().next() {
+            Some('<') => (Align::Left, &spec[1..]),
+            Some('>') => (Align::Right, &spec[1..]),
+            Some('^') => (Align::Center, &spec[1..]),
+            _ => (Align::Left, spec),
+        };
+        let width = if width.is_empty() {
+            0
+        } else {
+            width
+                .parse()
+                .map_err(|_| format!("invalid width `{spec}` of `{{{name}}}` in pattern"))?
+        };
+
+        Ok(Piece::Value {
+            placeholder,
+            format: None,
+            align,
+            width,
+        })
+    }
+
+    fn render(
+        &self,
+        to: &mut Writer,
+        value: impl Fn(Placeholder, Option<&str>) -> String,
+    ) -> Result<(), SendSyncError> {
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => write!(to, "{text}")?,
+                Piece::Value {
+                    placeholder,
+                    format,
+                    align,
+                    width,
+                } => {
+                    let value = value(*placeholder, format.as_deref());
+                    // by columns on screen, as wide characters take two
+                    let fill = width.saturating_sub(value.width());
+                    let (left, right) = match align {
+                        Align::Left => (0, fill),
+                        Align::Right => (fill, 0),
+                        Align::Center => (fill / 2, fill - fill / 2),
+                    };
+                    write!(to, "{:left$}{value}{:right$}", "", "")?;
+                }
+            }
+        }
+        writeln!(to)?;
+
+        Ok(())
+    }
+}
+
+fn time(t: SystemTime, format: Option<&str>) -> String {
+    DateTime::<Local>::from(t)
+        .format(format.unwrap_or("%+"))
+        .to_string()
+}
+
+fn span_path(span: &SpanRef) -> String {
+    span.path().join("/")
+}
+
+/// Formats logs and spans after templates such as
+/// `"{time:%H:%M:%S} {level:>5} [{span_path}] {message} {fields}"`.
+///
+/// - `time` takes a `chrono` format, `%+` by default; the others take an alignment
+///   (`<`, `>` or `^`) and a width in columns.
+/// - Logs: `time`, `level`, `message`, `fields` (`key=value`, separated by spaces),
+///   `span` (name of the current span) and `span_path` (names from the outermost span, joined by `/`).
+/// - Spans: `time` (when it started), `name`, `status`, `progress` (`3/10`), `elapsed`,
+///   `indent` (two spaces per level of nesting) and `span_path`.
+///
+/// Placeholders that don't apply are left empty; `{{` and `}}` are written as braces.
+#[derive(Debug, Clone)]
+pub struct PatternFormatter {
+    log: Pattern,
+    span: Pattern,
+}
+
+impl PatternFormatter {
+    pub const DEFAULT_SPAN: &'static str = "{indent}{status} {name} [{elapsed}]";
+
+    /// Spans are formatted after `DEFAULT_SPAN`.
+    pub fn new(log: &str) -> Result<Self, SendSyncError> {
+        Self::with_span(log, Self::DEFAULT_SPAN)
+    }
+
+    pub fn with_span(log: &str, span: &str) -> Result<Self, SendSyncError> {
+        Ok(Self {
+            log: Pattern::parse(log)?,
+            span: Pattern::parse(span)?,
+        })
+    }
+}
+
+impl Formatter<Log> for PatternFormatter {
+    fn format(&self, to: &mut Writer, log: &Log) -> Result<(), SendSyncError> {
+        self.log.render(to, |placeholder, format| match placeholder {
+            Placeholder::Time => time(log.timestamp(), format),
+            Placeholder::Level => log.level().to_string(),
+            Placeholder::Message => log.message().to_string(),
+            Placeholder::Fields => log
+                .fields()
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Placeholder::Span if log.span().depth() > 0 => log.span().value().name().to_string(),
+            Placeholder::SpanPath => span_path(log.span()),
+            _ => String::new(),
+        })
+    }
+}
+
+impl Formatter<SpanRef> for PatternFormatter {
+    fn format(&self, to: &mut Writer, span: &SpanRef) -> Result<(), SendSyncError> {
+        let value = span.value();
+        self.span.render(to, |placeholder, format| match placeholder {
+            Placeholder::Time => time(value.timestamp(), format),
+            Placeholder::Name => value.name().to_string(),
+            Placeholder::Status => value.status().as_str().to_string(),
+            Placeholder::Progress => value
+                .progress()
+                .map(|p| format!("{}/{}", p.progress(), p.total()))
+                .unwrap_or_default(),
+            Placeholder::Elapsed => format_duration(value.elapsed()),
+            Placeholder::Indent => "  ".repeat(span.depth().saturating_sub(1)),
+            Placeholder::SpanPath => span_path(span),
+            _ => String::new(),
+        })
+    }
+}
+
+impl FormatterSet for PatternFormatter {}
@@ -0,0 +1,380 @@
+use crate::error::SendSyncError;
+use crate::handler::AggregatedError;
+use crate::{Event, Filter, Handler, Level, SpanRef};
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::AcqRel;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct HandlerId(usize);
+
+impl HandlerId {
+    pub(crate) fn new() -> Self {
+        static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        Self(ID_COUNTER.fetch_add(1, AcqRel))
+    }
+}
+
+pub type HandlerFactory = Arc<dyn Fn() -> Box<dyn Handler> + Send + Sync>;
+
+/// What the global handler does with a handler that returned an error.
+#[derive(Clone)]
+pub enum FailurePolicy {
+    /// Drop the handler for good.
+    Remove,
+    /// Skip the handler for `backoff`, doubling on every consecutive failure,
+    /// and remove it after `max_retries` consecutive failures.
+    /// Events arriving while it's backing off aren't delivered to it.
+    Retry { max_retries: u32, backoff: Duration },
+    /// Replace the handler with a fresh one from the factory, which is skipped for `backoff`,
+    /// doubling on every consecutive failure, and remove it after `max_restarts` consecutive failures.
+    Restart {
+        factory: HandlerFactory,
+        max_restarts: u32,
+        backoff: Duration,
+    },
+    /// Keep the handler as if nothing happened.
+    Ignore,
+}
+
+impl Debug for FailurePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailurePolicy::Remove => write!(f, "Remove"),
+            FailurePolicy::Retry {
+                max_retries,
+                backoff,
+            } => f
+                .debug_struct("Retry")
+                .field("max_retries", max_retries)
+                .field("backoff", backoff)
+                .finish(),
+            FailurePolicy::Restart {
+                max_restarts,
+                backoff,
+                ..
+            } => f
+                .debug_struct("Restart")
+                .field("max_restarts", max_restarts)
+                .field("backoff", backoff)
+                .finish_non_exhaustive(),
+            FailurePolicy::Ignore => write!(f, "Ignore"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Recovery {
+    Removed,
+    Retrying { attempt: u32, after: Duration },
+    Restarted { attempt: u32, after: Duration },
+    Ignored,
+}
+
+pub struct HandlerFailure<'a> {
+    id: HandlerId,
+    error: &'a SendSyncError,
+    recovery: Recovery,
+}
+
+impl<'a> HandlerFailure<'a> {
+    pub fn id(&self) -> HandlerId {
+        self.id
+    }
+
+    pub fn error(&self) -> &'a SendSyncError {
+        self.error
+    }
+
+    pub fn recovery(&self) -> Recovery {
+        self.recovery
+    }
+}
+
+pub type ErrorCallback = Box<dyn Fn(&HandlerFailure) + Send>;
+
+pub(crate) fn default_error_callback() -> ErrorCallback {
+    Box::new(|failure| {
+        eprintln!(
+            "[kairoi] handler {:?} failed ({:?}): {}",
+            failure.id(),
+            failure.recovery(),
+            failure.error()
+        );
+    })
+}
+
+pub(crate) struct Slot {
+    id: HandlerId,
+    handler: Box<dyn Handler>,
+    policy: FailurePolicy,
+    failures: u32,
+    parked_until: Option<Instant>,
+    level: Option<Level>,
+    filter: Option<Filter>,
+    last_tick: Instant,
+    dirty: bool,
+}
+
+impl Slot {
+    pub(crate) fn new(handler: Box<dyn Handler>, policy: FailurePolicy) -> Self {
+        Self {
+            id: HandlerId::new(),
+            handler,
+            policy,
+            failures: 0,
+            parked_until: None,
+            level: None,
+            filter: None,
+            last_tick: Instant::now(),
+            dirty: true,
+        }
+    }
+
+    pub(crate) fn id(&self) -> HandlerId {
+        self.id
+    }
+
+    fn accepts(&self, event: &Event) -> bool {
+        if let (Some(max), Event::Log(log)) = (self.level, event)
+            && log.level() > max
+        {
+            return false;
+        }
+
+        self.filter.as_ref().is_none_or(|filter| filter(event))
+            && self.handler.interest(&event.metadata())
+    }
+
+    /// When the next tick is due, if this slot needs one at all.
+    fn next_tick(&self, frame: Duration, live: bool) -> Option<Instant> {
+        if !self.handler.wants_tick() || !(self.dirty || live || self.handler.animating()) {
+            return None;
+        }
+
+        let next = self.last_tick + self.handler.tick_interval().unwrap_or(frame);
+        Some(match self.parked_until {
+            Some(until) => next.max(until),
+            None => next,
+        })
+    }
+
+    fn is_parked(&self, now: Instant) -> bool {
+        self.parked_until.is_some_and(|until| now < until)
+    }
+
+    fn back_off(&mut self, backoff: Duration) -> Duration {
+        let after = backoff.saturating_mul(1 << (self.failures - 1).min(16));
+        self.parked_until = Some(Instant::now() + after);
+        after
+    }
+
+    /// Applies the policy; returns `false` if the slot must be removed,
+    /// along with errors raised while recovering.
+    fn recover(&mut self) -> (Recovery, bool, Vec<SendSyncError>) {
+        self.failures += 1;
+
+        match self.policy.clone() {
+            FailurePolicy::Remove => (Recovery::Removed, false, Vec::new()),
+            FailurePolicy::Retry {
+                max_retries,
+                backoff,
+            } => {
+                if self.failures > max_retries {
+                    return (Recovery::Removed, false, Vec::new());
+                }
+
+                let after = self.back_off(backoff);
+                let attempt = self.failures;
+                (Recovery::Retrying { attempt, after }, true, Vec::new())
+            }
+            FailurePolicy::Restart {
+                factory,
+                max_restarts,
+                backoff,
+            } => {
+                if self.failures > max_restarts {
+                    return (Recovery::Removed, false, Vec::new());
+                }
+
+                let mut errors = Vec::new();
+                if let Err(e) = self.handler.shutdown() {
+                    errors.push(e);
+                }
+                self.handler = factory();
+                self.dirty = true;
+
+                // a handler that can't even start is as good as gone
+                if let Err(e) = self.handler.on_start() {
+                    errors.push(e);
+                    return (Recovery::Removed, false, errors);
+                }
+
+                let after = self.back_off(backoff);
+                let attempt = self.failures;
+                (Recovery::Restarted { attempt, after }, true, errors)
+            }
+            FailurePolicy::Ignore => (Recovery::Ignored, true, Vec::new()),
+        }
+    }
+}
+
+pub(crate) struct Slots {
+    slots: Vec<Slot>,
+    on_error: ErrorCallback,
+}
+
+impl Slots {
+    pub(crate) fn new(slots: Vec<Slot>, on_error: ErrorCallback) -> Self {
+        Self { slots, on_error }
+    }
+
+    // `f` tells whether it called the handler at all; only calls that did count as successes
+    fn call<F: FnMut(&mut Slot) -> Result<bool, SendSyncError>>(
+        &mut self,
+        force: bool,
+        mut f: F,
+    ) -> Result<(), AggregatedError> {
+        let now = Instant::now();
+
+        let mut errors: Vec<SendSyncError> = Vec::new();
+        let on_error = &self.on_error;
+        self.slots.retain_mut(|slot| {
+            if !force && slot.is_parked(now) {
+                return true;
+            }
+
+            match f(slot) {
+                Ok(false) => true,
+                Ok(true) => {
+                    slot.failures = 0;
+                    slot.parked_until = None;
+                    true
+                }
+                Err(e) => {
+                    let (recovery, keep, more) = slot.recover();
+                    for error in std::iter::once(e).chain(more) {
+                        on_error(&HandlerFailure {
+                            id: slot.id,
+                            error: &error,
+                            recovery,
+                        });
+                        errors.push(error);
+                    }
+                    keep
+                }
+            }
+        });
+
+        if !errors.is_empty() {
+            return Err(AggregatedError::new(errors));
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` on every handler that isn't backing off, or on every handler if `force` is set.
+    /// Failures are reported to the error callback and returned.
+    pub(crate) fn foreach<F: Fn(&dyn Handler) -> Result<(), SendSyncError>>(
+        &mut self,
+        force: bool,
+        f: F,
+    ) -> Result<(), AggregatedError> {
+        self.call(force, |slot| f(slot.handler.as_ref()).map(|_| true))
+    }
+
+    pub(crate) fn handle(&mut self, event: &Event) -> Result<(), AggregatedError> {
+        self.call(false, |slot| {
+            if !slot.accepts(event) {
+                return Ok(false);
+            }
+
+            slot.dirty = true;
+            slot.handler.handle(event).map(|_| true)
+        })
+    }
+
+    /// Ticks every slot that is due, or every slot wanting ticks if `frame` is `None`.
+    pub(crate) fn tick(
+        &mut self,
+        root: &SpanRef,
+        frame: Option<Duration>,
+    ) -> Result<(), AggregatedError> {
+        let now = Instant::now();
+        let live = root.has_children();
+
+        self.call(false, |slot| {
+            let due = match frame {
+                Some(frame) => slot.next_tick(frame, live).is_some_and(|next| next <= now),
+                None => slot.handler.wants_tick(),
+            };
+            if !due {
+                return Ok(false);
+            }
+
+            slot.last_tick = now;
+            slot.dirty = false;
+            // frames say nothing about whether events get through, so they don't count as success
+            slot.handler.tick(root).map(|_| false)
+        })
+    }
+
+    pub(crate) fn mark_dirty(&mut self) {
+        for slot in &mut self.slots {
+            slot.dirty = true;
+        }
+    }
+
+    pub(crate) fn next_tick(&self, frame: Duration, live: bool) -> Option<Instant> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.next_tick(frame, live))
+            .min()
+    }
+
+    pub(crate) fn attach(&mut self, slot: Slot) -> Result<(), AggregatedError> {
+        let id = slot.id;
+        self.slots.push(slot);
+        self.call(true, |slot| {
+            if slot.id != id {
+                return Ok(false);
+            }
+            slot.handler.on_start().map(|_| true)
+        })
+    }
+
+    fn unknown(id: HandlerId) -> AggregatedError {
+        AggregatedError::single(format!("No handler with {id:?}"))
+    }
+
+    pub(crate) fn detach(&mut self, id: HandlerId) -> Result<(), AggregatedError> {
+        let Some(i) = self.slots.iter().position(|slot| slot.id == id) else {
+            return Err(Self::unknown(id));
+        };
+
+        let slot = self.slots.remove(i);
+        let flushed = slot.handler.flush().map_err(|e| AggregatedError::new(vec![e]));
+        let shutdown = slot.handler.shutdown().map_err(|e| AggregatedError::new(vec![e]));
+        AggregatedError::merge([flushed, shutdown])
+    }
+
+    pub(crate) fn ids(&self) -> Vec<HandlerId> {
+        self.slots.iter().map(|slot| slot.id).collect()
+    }
+
+    fn find(&mut self, id: HandlerId) -> Option<&mut Slot> {
+        self.slots.iter_mut().find(|slot| slot.id == id)
+    }
+
+    pub(crate) fn set_level(&mut self, id: HandlerId, level: Option<Level>) -> Result<(), AggregatedError> {
+        self.find(id).ok_or_else(|| Self::unknown(id))?.level = level;
+        Ok(())
+    }
+
+    pub(crate) fn set_filter(&mut self, id: HandlerId, filter: Option<Filter>) -> Result<(), AggregatedError> {
+        self.find(id).ok_or_else(|| Self::unknown(id))?.filter = filter;
+        Ok(())
+    }
+}
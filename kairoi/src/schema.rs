@@ -0,0 +1,56 @@
+use crate::{Id, SpanRef};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::time::SystemTime;
+
+// what `JsonFormatter`, `LogfmtFormatter` and the serde impls have in common
+
+/// RFC 3339 in UTC, to nanoseconds so that it reads back as it was.
+pub(crate) fn timestamp(t: SystemTime) -> String {
+    DateTime::<Utc>::from(t).to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+// the root counts as no span
+pub(crate) fn span_id(span: &SpanRef) -> Option<Id> {
+    Some(span).filter(|span| span.depth() > 0).map(|span| span.id())
+}
+
+pub(crate) fn parent_id(span: &SpanRef) -> Option<Id> {
+    span.parent().as_ref().and_then(span_id)
+}
+
+#[cfg(feature = "serde")]
+pub(crate) mod rfc3339 {
+    use chrono::DateTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(t: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::timestamp(*t))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DateTime::parse_from_rfc3339(&s)
+            .map(SystemTime::from)
+            .map_err(serde::de::Error::custom)
+    }
+
+    pub mod option {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::SystemTime;
+
+        pub fn serialize<S: Serializer>(t: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error> {
+            match t {
+                Some(t) => super::serialize(t, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<SystemTime>, D::Error> {
+            #[derive(Deserialize)]
+            struct Time(#[serde(with = "super")] SystemTime);
+
+            Ok(Option::<Time>::deserialize(deserializer)?.map(|Time(t)| t))
+        }
+    }
+}
@@ -1,10 +1,12 @@
-use crate::{Event, Node};
-use lazy_static::lazy_static;
+use crate::{Dispatcher, Event, Id, Node};
+use std::fmt::{Display, Formatter};
+use std::future::Future;
 use std::sync::{Arc, Weak};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use tokio::task_local;
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Progress {
     total: u64,
     progress: u64,
@@ -24,11 +26,43 @@ impl Progress {
     }
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Status {
+    Running,
+    Completed,
+    /// Marked by `Scope::fail`.
+    Failed,
+    /// A panic was raised while this was the current span.
+    Panicked,
+    /// The future was dropped before it finished.
+    Cancelled,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Running => "running",
+            Status::Completed => "completed",
+            Status::Failed => "failed",
+            Status::Panicked => "panicked",
+            Status::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// With the `serde` feature, `timestamp` and `ended` are written in RFC 3339, as `JsonFormatter` does.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
+    #[cfg_attr(feature = "serde", serde(with = "crate::schema::rfc3339"))]
     timestamp: SystemTime,
     name: String,
     progress: Option<Progress>,
+    status: Status,
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::schema::rfc3339::option"))]
+    ended: Option<SystemTime>,
 }
 
 pub type SpanRef = Arc<Node<Span>>;
@@ -46,6 +80,12 @@ impl Span {
         clone
     }
 
+    pub fn with_status(&self, status: Status) -> Self {
+        let mut clone = self.clone();
+        clone.status = status;
+        clone
+    }
+
     pub fn timestamp(&self) -> SystemTime {
         self.timestamp
     }
@@ -57,6 +97,36 @@ impl Span {
     pub fn progress(&self) -> Option<Progress> {
         self.progress
     }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// When the scope of the span ended; `None` while it's running.
+    pub fn ended(&self) -> Option<SystemTime> {
+        self.ended
+    }
+
+    /// Time from `timestamp` to the end of the span, or to now if it's still running.
+    pub fn elapsed(&self) -> Duration {
+        let end = self.ended.unwrap_or_else(SystemTime::now);
+        end.duration_since(self.timestamp).unwrap_or_default()
+    }
+
+    fn end(&self, finished: bool) -> Self {
+        let mut clone = self.clone();
+        if clone.status == Status::Running {
+            clone.status = if std::thread::panicking() {
+                Status::Panicked
+            } else if finished {
+                Status::Completed
+            } else {
+                Status::Cancelled
+            };
+        }
+        clone.ended = Some(SystemTime::now());
+        clone
+    }
 }
 
 impl Default for Span {
@@ -65,14 +135,12 @@ impl Default for Span {
             timestamp: SystemTime::now(),
             name: String::default(),
             progress: None,
+            status: Status::Running,
+            ended: None,
         }
     }
 }
 
-lazy_static! {
-    static ref ROOT: SpanRef = Arc::new(Node::new(Span::default()));
-}
-
 task_local! {
     static CURRENT: Weak<Node<Span >>;
 }
@@ -83,15 +151,18 @@ impl Span {
 
         Event::span_begin(new.clone()).submit();
 
-        let new_clone = new.clone();
+        // ends the span even if `f` panics or the future is dropped half-way
+        let mut guard = ScopeGuard {
+            node: new.clone(),
+            finished: false,
+        };
+
         let v = CURRENT
-            .scope(Arc::downgrade(&new_clone), async move {
-                f(Scope::new(new_clone)).await
-            })
+            .scope(Arc::downgrade(&new), async move { f(Scope::new(new)).await })
             .await;
 
-        new.delete();
-        Event::span_end(new).submit();
+        guard.finished = true;
+        drop(guard);
 
         v
     }
@@ -101,11 +172,34 @@ impl Span {
             .try_with(|v| v.upgrade())
             .ok()
             .flatten()
-            .unwrap_or_else(|| ROOT.clone())
+            .unwrap_or_else(Self::root)
     }
 
+    /// Root of the span tree of the current dispatcher.
     pub fn root() -> SpanRef {
-        ROOT.clone()
+        Dispatcher::with_current(Dispatcher::root)
+    }
+
+    // spans of one dispatcher must not leak into the tree of another
+    pub(crate) fn detached<T, F: FnOnce() -> T>(f: F) -> T {
+        CURRENT.sync_scope(Weak::new(), f)
+    }
+
+    pub(crate) async fn detached_async<F: Future>(f: F) -> F::Output {
+        CURRENT.scope(Weak::new(), f).await
+    }
+}
+
+struct ScopeGuard {
+    node: SpanRef,
+    finished: bool,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        self.node.update(self.node.value().end(self.finished));
+        self.node.delete();
+        Event::span_end(self.node.clone()).submit();
     }
 }
 
@@ -118,7 +212,160 @@ impl Scope {
         Self { node }
     }
 
+    /// A failed span stays failed, whatever the status of `data`.
     pub fn update(&self, data: Span) {
+        let data = if self.node.value().status() == Status::Failed {
+            data.with_status(Status::Failed)
+        } else {
+            data
+        };
         self.node.update(data);
     }
+
+    /// Marks the span as `Status::Failed`, for good.
+    pub fn fail(&self) {
+        self.node.update(self.node.value().with_status(Status::Failed));
+    }
+}
+
+/// Order of sibling spans.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum SpanOrder {
+    /// The order they were entered in.
+    #[default]
+    Inserted,
+    /// By `Span::timestamp`, which `Scope::update` may have changed.
+    StartTime,
+    Name,
+    /// Furthest along first; spans without progress go last.
+    Progress,
+}
+
+impl SpanOrder {
+    /// Stable, so equal spans keep the order they were entered in.
+    pub fn sort(&self, spans: &mut [SpanRef]) {
+        match self {
+            SpanOrder::Inserted => {}
+            SpanOrder::StartTime => spans.sort_by_key(|span| span.value().timestamp()),
+            SpanOrder::Name => spans.sort_by(|a, b| a.value().name().cmp(b.value().name())),
+            SpanOrder::Progress => spans.sort_by(|a, b| {
+                let done = |span: &SpanRef| {
+                    span.value()
+                        .progress()
+                        .filter(|p| p.total() > 0)
+                        .map(|p| p.progress() as f64 / p.total() as f64)
+                        .unwrap_or(-1.0)
+                };
+                done(b).total_cmp(&done(a))
+            }),
+        }
+    }
+}
+
+impl Node<Span> {
+    pub fn sorted_children(&self, order: SpanOrder) -> Vec<SpanRef> {
+        let mut children: Vec<_> = self.children().collect();
+        order.sort(&mut children);
+        children
+    }
+
+    /// Names from the outermost span down to this one; the root isn't included.
+    pub fn path(&self) -> Vec<String> {
+        let mut path = Vec::new();
+        if self.depth() > 0 {
+            path.push(self.value().name().to_string());
+        }
+
+        let mut parent = self.parent();
+        while let Some(node) = parent {
+            if node.depth() == 0 {
+                break;
+            }
+            path.push(node.value().name().to_string());
+            parent = node.parent();
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+/// Owned copy of a span subtree at one point in time.
+///
+/// With the `serde` feature, it's written as the fields of its span along with
+/// `span_id` and `children`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpanSnapshot {
+    #[cfg_attr(feature = "serde", serde(rename = "span_id"))]
+    id: Id,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    span: Span,
+    children: Vec<SpanSnapshot>,
+}
+
+impl SpanSnapshot {
+    pub fn capture(node: &SpanRef) -> Self {
+        Self::capture_sorted(node, SpanOrder::Inserted)
+    }
+
+    pub fn capture_sorted(node: &SpanRef, order: SpanOrder) -> Self {
+        Self {
+            id: node.id(),
+            span: node.value().as_ref().clone(),
+            children: node
+                .sorted_children(order)
+                .iter()
+                .map(|child| Self::capture_sorted(child, order))
+                .collect(),
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn children(&self) -> &[SpanSnapshot] {
+        &self.children
+    }
+
+    fn fmt_at(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        for _ in 0..depth {
+            write!(f, "  ")?;
+        }
+
+        write!(f, "{}", self.span.name())?;
+        if let Some(p) = self.span.progress() {
+            write!(f, " ({}/{})", p.progress(), p.total())?;
+        }
+        // spans that have ended, like those in the report of a failed test
+        if self.span.status() != Status::Running {
+            write!(f, " [{}]", self.span.status().as_str())?;
+        }
+        writeln!(f)?;
+
+        for child in &self.children {
+            child.fmt_at(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for SpanSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // the root of a dispatcher has no name; print its children as top-level spans
+        if self.span.name().is_empty() {
+            for child in &self.children {
+                child.fmt_at(f, 0)?;
+            }
+            return Ok(());
+        }
+
+        self.fmt_at(f, 0)
+    }
 }
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+use std::env;
+use unicode_width::UnicodeWidthChar;
+
+/// How output to a stream should look, judging by the stream and the environment.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Terminal {
+    interactive: bool,
+    color: bool,
+}
+
+fn env_set(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+impl Terminal {
+    pub fn new(interactive: bool, color: bool) -> Self {
+        Self { interactive, color }
+    }
+
+    /// `is_terminal` tells whether the stream is attached to a terminal.
+    ///
+    /// - `TERM=dumb` turns off cursor movement.
+    /// - `NO_COLOR` turns off colors.
+    /// - `CLICOLOR_FORCE` turns on colors even if the stream isn't a terminal.
+    pub fn detect(is_terminal: bool) -> Self {
+        let dumb = env::var("TERM").is_ok_and(|term| term == "dumb");
+        let interactive = is_terminal && !dumb;
+
+        let color = if env_set("NO_COLOR").is_some() {
+            false
+        } else if env_set("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+            true
+        } else {
+            interactive
+        };
+
+        Self { interactive, color }
+    }
+
+    /// Whether the live span tree can be redrawn in place.
+    pub fn interactive(&self) -> bool {
+        self.interactive
+    }
+
+    pub fn color(&self) -> bool {
+        self.color
+    }
+}
+
+// skips escape sequences, which take no columns
+fn visible(line: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut chars = line.char_indices();
+    std::iter::from_fn(move || {
+        loop {
+            let (i, c) = chars.next()?;
+            if c != '\x1b' {
+                return Some((i, c));
+            }
+            if chars.next().is_some_and(|(_, c)| c == '[') {
+                chars.find(|(_, c)| ('\x40'..='\x7e').contains(c));
+            }
+        }
+    })
+}
+
+pub(crate) fn display_width(line: &str) -> usize {
+    visible(line).map(|(_, c)| c.width().unwrap_or(0)).sum()
+}
+
+/// Cuts `line` to `width` columns, ending it with `…` if anything was cut.
+pub(crate) fn truncate(line: &str, width: usize) -> Cow<'_, str> {
+    if display_width(line) <= width {
+        return Cow::Borrowed(line);
+    }
+
+    let mut used = 0;
+    let mut end = 0;
+    for (i, c) in visible(line) {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        used += w;
+        end = i + c.len_utf8();
+    }
+
+    let mut cut = line[..end].to_owned();
+    if width > 0 {
+        cut.push('…');
+    }
+    if line.contains('\x1b') {
+        cut.push_str("\x1b[0m");
+    }
+    Cow::Owned(cut)
+}
+
+#[cfg(feature = "tui")]
+pub(crate) fn strip_escapes(line: &str) -> String {
+    visible(line).map(|(_, c)| c).collect()
+}
+
+/// `line` cut or padded with spaces to exactly `width` columns.
+#[cfg(feature = "tui")]
+pub(crate) fn fit(line: &str, width: usize) -> String {
+    let mut line = truncate(line, width).into_owned();
+    let pad = width.saturating_sub(display_width(&line));
+    line.extend(std::iter::repeat_n(' ', pad));
+    line
+}
@@ -0,0 +1,400 @@
+use crate::error::SendSyncError;
+use crate::schema;
+use crate::{
+    DefaultGuard, Dispatcher, Event, GlobalHandler, Handler, HandlerControl, Id, Level, Log, Node,
+    Span, SpanRef, SpanSnapshot,
+};
+use parking_lot::Mutex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+
+// kept out of the crate root, where a glob import would clash with the builtin `#[test]`
+pub use kairoi_macros::test;
+
+#[derive(Debug, Clone)]
+pub struct CapturedSpan {
+    id: Id,
+    parent: Option<Id>,
+    path: Vec<String>,
+    span: Span,
+    completed: bool,
+}
+
+impl CapturedSpan {
+    fn new(node: &SpanRef, completed: bool) -> Self {
+        Self {
+            id: node.id(),
+            parent: schema::parent_id(node),
+            path: node.path(),
+            span: node.value().as_ref().clone(),
+            completed,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Names from the outermost span down to this one.
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+
+    /// Whether the path ends with `names`, e.g. `["fetch", "parse"]` for `parse` within `fetch`.
+    pub fn matches<S: AsRef<str>>(&self, names: &[S]) -> bool {
+        names.len() <= self.path.len()
+            && self.path[self.path.len() - names.len()..]
+                .iter()
+                .zip(names)
+                .all(|(a, b)| a == b.as_ref())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    events: Vec<Event>,
+    completed: HashMap<Id, CapturedSpan>,
+    root: Option<SpanRef>,
+}
+
+/// Records every event and the span tree it is ticked with.
+#[derive(Clone, Default)]
+pub struct CaptureHandler {
+    state: Arc<Mutex<State>>,
+}
+
+impl CaptureHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.state.lock().events.clone()
+    }
+
+    pub fn logs(&self) -> Vec<Arc<Log>> {
+        self.state
+            .lock()
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Log(log) => Some(log.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Completed spans followed by the running ones.
+    pub fn spans(&self) -> Vec<CapturedSpan> {
+        let state = self.state.lock();
+
+        let mut spans: Vec<CapturedSpan> = state.completed.values().cloned().collect();
+        spans.sort_by_key(|span| span.id);
+
+        if let Some(root) = &state.root {
+            for node in root.find_all(|node| node.depth() > 0) {
+                spans.push(CapturedSpan::new(&node, false));
+            }
+        }
+
+        spans
+    }
+
+    /// Snapshot of the span tree at the time of the call; `None` until the first tick.
+    pub fn tree(&self) -> Option<SpanSnapshot> {
+        self.state.lock().root.as_ref().map(SpanSnapshot::capture)
+    }
+
+    // ended spans are gone from the live tree, so it's built anew from the captured ones
+    fn all_spans(&self) -> SpanSnapshot {
+        let mut spans = self.spans();
+        // parents are entered, and so numbered, before their children
+        spans.sort_by_key(|span| span.id);
+
+        let root: SpanRef = Arc::new(Node::new(Span::default()));
+        let mut nodes: HashMap<Id, SpanRef> = HashMap::new();
+        for span in spans {
+            let parent = span.parent.and_then(|id| nodes.get(&id)).unwrap_or(&root);
+            let node = parent.add(Node::new(span.span));
+            nodes.insert(span.id, node);
+        }
+
+        SpanSnapshot::capture(&root)
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.events.clear();
+        state.completed.clear();
+    }
+}
+
+impl Handler for CaptureHandler {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        let mut state = self.state.lock();
+        if let Event::SpanEnd(span) = event {
+            state.completed.insert(span.id(), CapturedSpan::new(span, true));
+        }
+        state.events.push(event.clone());
+
+        Ok(())
+    }
+
+    fn tick(&self, root: &SpanRef) -> Result<(), SendSyncError> {
+        self.state.lock().root = Some(root.clone());
+        Ok(())
+    }
+}
+
+/// A `CaptureHandler` bound to the handler thread feeding it.
+/// Every read waits for the events submitted so far to be captured.
+#[derive(Clone)]
+pub struct CaptureRef {
+    handler: CaptureHandler,
+    control: HandlerControl,
+}
+
+impl CaptureRef {
+    pub fn sync(&self) -> &CaptureHandler {
+        if let Err(e) = self.control.flush() {
+            panic!("[kairoi] failed to flush captured events\n{e}");
+        }
+        &self.handler
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.sync().events()
+    }
+
+    pub fn logs(&self) -> Vec<Arc<Log>> {
+        self.sync().logs()
+    }
+
+    pub fn spans(&self) -> Vec<CapturedSpan> {
+        self.sync().spans()
+    }
+
+    pub fn tree(&self) -> Option<SpanSnapshot> {
+        self.sync().tree()
+    }
+
+    pub fn clear(&self) {
+        self.sync().clear()
+    }
+
+    /// Plain-text listing of the captured logs and the span tree, for test failure output.
+    pub fn report(&self) -> String {
+        // may run while unwinding; a failed flush must not panic again
+        let _ = self.control.flush();
+        let handler = &self.handler;
+
+        let mut report = String::from("--- kairoi logs ---\n");
+        for log in handler.logs() {
+            let path = log.span().path();
+            if path.is_empty() {
+                report.push_str(&format!("{:>5}: {}\n", log.level(), log.message()));
+            } else {
+                report.push_str(&format!(
+                    "{:>5} [{}]: {}\n",
+                    log.level(),
+                    path.join(" > "),
+                    log.message()
+                ));
+            }
+        }
+
+        report.push_str("--- kairoi spans ---\n");
+        report.push_str(&handler.all_spans().to_string());
+
+        report
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CaptureRef>> = const { RefCell::new(None) };
+}
+
+/// Captures everything logged on the current thread until dropped.
+/// See `capture`.
+pub struct Capture {
+    capture: CaptureRef,
+    prev: Option<CaptureRef>,
+    _guard: DefaultGuard,
+    global: GlobalHandler,
+}
+
+/// Installs a fresh dispatcher with a `CaptureHandler` as the default of the current thread.
+///
+/// The capture also becomes the one used by `assert_logged!` and `assert_span!`
+/// when no capture is given to them.
+pub fn capture() -> Capture {
+    let dispatcher = Dispatcher::new();
+    let handler = CaptureHandler::new();
+    let global = GlobalHandler::builder()
+        .dispatcher(dispatcher.clone())
+        .handler(Box::new(handler.clone()))
+        .build()
+        .expect("a fresh dispatcher is never claimed");
+
+    let capture = CaptureRef {
+        handler,
+        control: global.control(),
+    };
+    let prev = CURRENT.with(|c| c.borrow_mut().replace(capture.clone()));
+
+    Capture {
+        capture,
+        prev,
+        _guard: dispatcher.set_default(),
+        global,
+    }
+}
+
+/// The capture installed last on the current thread.
+pub fn current() -> Option<CaptureRef> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+impl Capture {
+    pub fn dispatcher(&self) -> &Dispatcher {
+        self.global.dispatcher()
+    }
+
+    /// Runs `f` with the captured dispatcher as the dispatcher of the current task;
+    /// needed for tasks that may run on other threads.
+    pub async fn scope<F: Future>(&self, f: F) -> F::Output {
+        self.dispatcher().scope(f).await
+    }
+}
+
+impl Deref for Capture {
+    type Target = CaptureRef;
+
+    fn deref(&self) -> &Self::Target {
+        &self.capture
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        let _ = CURRENT.try_with(|c| *c.borrow_mut() = prev);
+    }
+}
+
+#[doc(hidden)]
+pub fn __current_or_panic() -> CaptureRef {
+    current().expect("[kairoi] no capture installed on this thread; call kairoi::testing::capture()")
+}
+
+#[doc(hidden)]
+pub fn __assert_logged(capture: &CaptureRef, level: Level, needle: &str, exact: bool) {
+    let logs = capture.logs();
+    let found = logs.iter().any(|log| {
+        log.level() == level
+            && if exact {
+                log.message() == needle
+            } else {
+                log.message().contains(needle)
+            }
+    });
+
+    if !found {
+        let mut dump = String::new();
+        for log in &logs {
+            dump.push_str(&format!("\n  {:?}: {}", log.level(), log.message()));
+        }
+
+        let how = if exact { "equal to" } else { "containing" };
+        panic!("no {level:?} log {how} {needle:?} was captured; captured logs:{dump}");
+    }
+}
+
+#[doc(hidden)]
+pub fn __assert_span(capture: &CaptureRef, path: &[&str], completed: Option<bool>) {
+    let spans = capture.spans();
+    let found = spans
+        .iter()
+        .any(|span| span.matches(path) && completed.is_none_or(|c| span.completed() == c));
+
+    if !found {
+        let state = match completed {
+            Some(true) => "completed ",
+            Some(false) => "running ",
+            None => "",
+        };
+
+        let mut dump = String::new();
+        for span in &spans {
+            let state = if span.completed() { "completed" } else { "running" };
+            dump.push_str(&format!("\n  {} ({state})", span.path().join(" > ")));
+        }
+
+        panic!(
+            "no {state}span {:?} was captured; captured spans:{dump}",
+            path.join(" > ")
+        );
+    }
+}
+
+#[doc(hidden)]
+pub trait __Outcome {
+    fn failed(&self) -> bool;
+}
+
+impl __Outcome for () {
+    fn failed(&self) -> bool {
+        false
+    }
+}
+
+impl<T, E> __Outcome for Result<T, E> {
+    fn failed(&self) -> bool {
+        self.is_err()
+    }
+}
+
+/// Prints the capture when the test fails: on unwind, or when `finish` sees an error.
+#[doc(hidden)]
+pub struct __Report {
+    capture: CaptureRef,
+    should_panic: bool,
+    finished: bool,
+}
+
+impl __Report {
+    pub fn new(capture: &Capture, should_panic: bool) -> Self {
+        Self {
+            capture: (*capture).clone(),
+            should_panic,
+            finished: false,
+        }
+    }
+
+    pub fn finish<T: __Outcome>(mut self, outcome: &T) {
+        self.finished = true;
+        if outcome.failed() {
+            // stdout is captured by libtest and shown with the failure
+            print!("{}", self.capture.report());
+        }
+    }
+}
+
+impl Drop for __Report {
+    fn drop(&mut self) {
+        if !self.finished && std::thread::panicking() && !self.should_panic {
+            print!("{}", self.capture.report());
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::error::SendSyncError;
+use crate::format::check_time_format;
+use crate::Level;
+use std::time::Duration;
+
+/// Foreground color of a styled piece of text.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Gray,
+    /// One of the 256 colors of the extended palette.
+    Fixed(u8),
+    /// 24-bit color, for terminals that support truecolor.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    pub(crate) fn escape(&self) -> String {
+        match self {
+            Color::Black => "\x1b[30m".to_string(),
+            Color::Red => "\x1b[31m".to_string(),
+            Color::Green => "\x1b[32m".to_string(),
+            Color::Yellow => "\x1b[33m".to_string(),
+            Color::Blue => "\x1b[34m".to_string(),
+            Color::Magenta => "\x1b[35m".to_string(),
+            Color::Cyan => "\x1b[36m".to_string(),
+            Color::White => "\x1b[37m".to_string(),
+            Color::Gray => "\x1b[90m".to_string(),
+            Color::Fixed(n) => format!("\x1b[38;5;{n}m"),
+            Color::Rgb(r, g, b) => format!("\x1b[38;2;{r};{g};{b}m"),
+        }
+    }
+}
+
+/// How the time of a log is shown.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Timestamp {
+    /// Local time, with a `chrono` format string.
+    Local(String),
+    /// UTC, with a `chrono` format string.
+    Utc(String),
+    /// Seconds since the `DefaultFormatter` was created, which is usually when the
+    /// handler using it was built rather than when the process started.
+    Relative,
+    None,
+}
+
+/// Drawn before a nested span, one per ancestor below the top level.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TreeGlyphs {
+    /// Before a span followed by a sibling.
+    pub branch: String,
+    /// Before the last span among its siblings.
+    pub last: String,
+    /// Below an ancestor followed by a sibling.
+    pub vertical: String,
+    /// Below the last ancestor among its siblings.
+    pub space: String,
+}
+
+impl TreeGlyphs {
+    /// Plain indentation of `width` spaces.
+    pub fn indent(width: usize) -> Self {
+        let indent = " ".repeat(width);
+        Self {
+            branch: indent.clone(),
+            last: indent.clone(),
+            vertical: indent.clone(),
+            space: indent,
+        }
+    }
+
+    /// `├─`, `└─` and `│`.
+    pub fn lines() -> Self {
+        Self {
+            branch: "├─ ".to_string(),
+            last: "└─ ".to_string(),
+            vertical: "│  ".to_string(),
+            space: "   ".to_string(),
+        }
+    }
+}
+
+/// Colors, labels and glyphs of `DefaultFormatter`.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    levels: [(String, Color); 5],
+    spinner: Vec<String>,
+    spinner_interval: Duration,
+    tree: TreeGlyphs,
+    timestamp: Timestamp,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            levels: [
+                (Level::Error.as_str().to_string(), Color::Red),
+                (Level::Warn.as_str().to_string(), Color::Yellow),
+                (Level::Info.as_str().to_string(), Color::Green),
+                (Level::Debug.as_str().to_string(), Color::Cyan),
+                (Level::Trace.as_str().to_string(), Color::Magenta),
+            ],
+            spinner: ["⠖", "⠲", "⠴", "⠦"].map(str::to_string).to_vec(),
+            spinner_interval: Duration::from_millis(100),
+            tree: TreeGlyphs::indent(2),
+            timestamp: Timestamp::Local("%+".to_string()),
+        }
+    }
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn level(mut self, level: Level, label: impl Into<String>, color: Color) -> Self {
+        self.levels[level as usize] = (label.into(), color);
+        self
+    }
+
+    /// Frames of running spans, each shown for `interval`.
+    /// An empty `frames` keeps the current ones.
+    pub fn spinner<S: Into<String>>(
+        mut self,
+        frames: impl IntoIterator<Item = S>,
+        interval: Duration,
+    ) -> Self {
+        let frames: Vec<String> = frames.into_iter().map(Into::into).collect();
+        if !frames.is_empty() {
+            self.spinner = frames;
+        }
+        self.spinner_interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    pub fn tree(mut self, tree: TreeGlyphs) -> Self {
+        self.tree = tree;
+        self
+    }
+
+    /// Fails if the format string of `Local` or `Utc` is invalid.
+    pub fn timestamp(mut self, timestamp: Timestamp) -> Result<Self, SendSyncError> {
+        if let Timestamp::Local(format) | Timestamp::Utc(format) = &timestamp {
+            check_time_format(format)?;
+        }
+        self.timestamp = timestamp;
+        Ok(self)
+    }
+
+    pub(crate) fn level_label(&self, level: Level) -> &str {
+        &self.levels[level as usize].0
+    }
+
+    pub(crate) fn level_color(&self, level: Level) -> Color {
+        self.levels[level as usize].1
+    }
+
+    // labels are padded to the widest one
+    pub(crate) fn label_width(&self) -> usize {
+        self.levels.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0)
+    }
+
+    pub(crate) fn spinner_frame(&self, elapsed: Duration) -> &str {
+        let i = elapsed.as_millis() / self.spinner_interval.as_millis();
+        &self.spinner[(i % self.spinner.len() as u128) as usize]
+    }
+
+    pub(crate) fn tree_glyphs(&self) -> &TreeGlyphs {
+        &self.tree
+    }
+
+    pub(crate) fn timestamp_format(&self) -> &Timestamp {
+        &self.timestamp
+    }
+}
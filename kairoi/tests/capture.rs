@@ -0,0 +1,115 @@
+use kairoi::testing::capture;
+use kairoi::{assert_logged, assert_span, info, warn, Level, Scope, Span};
+
+async fn named<T>(name: &str, f: impl AsyncFnOnce(Scope) -> T) -> T {
+    Span::scope(async |s: Scope| {
+        s.update(Span::default().with_name(name.to_string()));
+        f(s).await
+    })
+    .await
+}
+
+#[tokio::test]
+async fn captures_logs_and_spans() {
+    let capture = capture();
+
+    info!("plain");
+    named("fetch", async |_| {
+        named("parse", async |_| warn!("retry 1 of 3")).await;
+    })
+    .await;
+
+    assert_logged!(Level::Info, "plain");
+    assert_logged!(Level::Warn, contains "retry");
+    assert_logged!(capture, Level::Warn, "retry 1 of 3");
+
+    assert_span!("fetch" > "parse", completed);
+    assert_span!("parse");
+    assert_span!(capture, "fetch", completed);
+
+    let logs = capture.logs();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[1].span().path(), ["fetch", "parse"]);
+}
+
+#[tokio::test]
+async fn captures_running_spans() {
+    let capture = capture();
+
+    named("serve", async |_| {
+        assert_span!(capture, "serve", running);
+    })
+    .await;
+
+    assert_span!(capture, "serve", completed);
+}
+
+#[tokio::test]
+#[should_panic(expected = "no Error log containing \"retry\"")]
+async fn logs_of_another_level_do_not_match() {
+    let _capture = capture();
+
+    warn!("retry 1 of 3");
+    assert_logged!(Level::Error, contains "retry");
+}
+
+#[tokio::test]
+#[should_panic(expected = "no span \"fetch > parse\"")]
+async fn spans_must_match_the_whole_path() {
+    let _capture = capture();
+
+    named("parse", async |_| {}).await;
+    assert_span!("fetch" > "parse");
+}
+
+#[tokio::test]
+async fn clear_forgets_captured_events() {
+    let capture = capture();
+
+    info!("before");
+    capture.clear();
+    info!("after");
+
+    let messages: Vec<String> = capture.logs().iter().map(|log| log.message().to_string()).collect();
+    assert_eq!(messages, ["after"]);
+}
+
+#[kairoi::testing::test]
+fn test_attribute_installs_a_capture() {
+    info!("inside");
+    assert_logged!(Level::Info, "inside");
+}
+
+#[kairoi::testing::test]
+#[tokio::test]
+async fn test_attribute_runs_async_tests() -> Result<(), String> {
+    named("fetch", async |_| info!("fetching")).await;
+
+    assert_span!("fetch", completed);
+    Ok(())
+}
+
+#[kairoi::testing::test]
+#[should_panic(expected = "no Warn log")]
+fn test_attribute_keeps_should_panic() {
+    info!("not a warning");
+    assert_logged!(Level::Warn, contains "warning");
+}
+
+#[tokio::test]
+async fn reports_the_span_tree() {
+    let capture = capture();
+
+    named("fetch", async |_| {
+        named("parse", async |_| info!("parsing")).await;
+        named("store", async |_| {}).await;
+    })
+    .await;
+
+    let report = named("serve", async |_| capture.report()).await;
+    let spans = report.split_once("--- kairoi spans ---\n").unwrap().1;
+    assert_eq!(
+        spans,
+        "fetch [completed]\n  parse [completed]\n  store [completed]\nserve\n"
+    );
+}
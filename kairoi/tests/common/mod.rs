@@ -0,0 +1,51 @@
+// shared by the test crates; each uses only some of it
+#![allow(dead_code)]
+
+use kairoi::{Event, Handler, Level, Log, Metadata, SendSyncError, SpanRef};
+use std::sync::{Arc, Mutex};
+
+/// An info log outside of any span.
+pub fn log(message: &str, fields: &[(&str, &str)]) -> Arc<Log> {
+    let fields = fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    match Event::log_with_fields(Level::Info, message.to_string(), fields) {
+        Event::Log(log) => log,
+        _ => unreachable!(),
+    }
+}
+
+/// Keeps the messages of the logs it handles; with `level`, it takes interest only in logs up to it.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    pub level: Option<Level>,
+    pub messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl Recorder {
+    pub fn at(level: Level) -> Self {
+        Self {
+            level: Some(level),
+            ..Self::default()
+        }
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+impl Handler for Recorder {
+    fn handle(&self, event: &Event) -> Result<(), SendSyncError> {
+        if let Event::Log(log) = event {
+            self.messages.lock().unwrap().push(log.message().to_string());
+        }
+        Ok(())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    fn interest(&self, metadata: &Metadata) -> bool {
+        self.level.is_none_or(|level| metadata.level().is_none_or(|l| l <= level))
+    }
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::Recorder;
+use kairoi::{info, warn, Dispatcher, Event, GlobalHandler, Level};
+use std::sync::Arc;
+
+fn start(recorder: &Recorder) -> (Dispatcher, GlobalHandler) {
+    let dispatcher = Dispatcher::new();
+    let global = GlobalHandler::builder()
+        .dispatcher(dispatcher.clone())
+        .handler(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    (dispatcher, global)
+}
+
+#[test]
+fn attached_handlers_get_events_until_detached() {
+    let first = Recorder::default();
+    let (dispatcher, global) = start(&first);
+    let control = global.control();
+
+    dispatcher.with(|| info!("one"));
+    global.flush().unwrap();
+
+    let second = Recorder::default();
+    let id = control.attach(Box::new(second.clone())).unwrap();
+    assert_eq!(control.handlers().unwrap(), [global.handler_ids()[0], id]);
+
+    dispatcher.with(|| info!("two"));
+    global.flush().unwrap();
+
+    control.detach(id).unwrap();
+    assert_eq!(control.handlers().unwrap(), global.handler_ids());
+
+    dispatcher.with(|| info!("three"));
+    global.flush().unwrap();
+
+    assert_eq!(first.messages(), ["one", "two", "three"]);
+    assert_eq!(second.messages(), ["two"]);
+}
+
+#[test]
+fn levels_and_filters_apply_to_one_handler() {
+    let first = Recorder::default();
+    let (dispatcher, global) = start(&first);
+    let control = global.control();
+    let second = Recorder::default();
+    let id = control.attach(Box::new(second.clone())).unwrap();
+
+    control.set_level(global.handler_ids()[0], Some(Level::Warn)).unwrap();
+    control
+        .set_filter(id, Some(Arc::new(|event: &Event| match event {
+            Event::Log(log) => log.message().starts_with("keep"),
+            _ => true,
+        })))
+        .unwrap();
+
+    dispatcher.with(|| {
+        info!("keep info");
+        warn!("keep warn");
+        warn!("drop warn");
+    });
+    global.flush().unwrap();
+
+    assert_eq!(first.messages(), ["keep warn", "drop warn"]);
+    assert_eq!(second.messages(), ["keep info", "keep warn"]);
+
+    control.set_level(global.handler_ids()[0], None).unwrap();
+    control.set_filter(id, None).unwrap();
+
+    dispatcher.with(|| info!("again"));
+    global.flush().unwrap();
+
+    assert_eq!(first.messages().last().unwrap(), "again");
+    assert_eq!(second.messages().last().unwrap(), "again");
+}
+
+#[test]
+fn unknown_handlers_are_reported() {
+    let (_dispatcher, global) = start(&Recorder::default());
+    let control = global.control();
+
+    let id = control.attach(Box::new(Recorder::default())).unwrap();
+    control.detach(id).unwrap();
+
+    let errors = [
+        control.detach(id),
+        control.set_level(id, Some(Level::Warn)),
+        control.set_filter(id, None),
+    ];
+    for error in errors {
+        let error = error.unwrap_err().to_string();
+        assert!(error.contains(&format!("No handler with {id:?}")), "{error}");
+    }
+}
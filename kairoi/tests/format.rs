@@ -0,0 +1,39 @@
+use kairoi::{
+    DefaultFormatter, FormatterSet, Node, Span, SpanRef, Theme, Timestamp, TreeGlyphs, Writer,
+};
+use std::sync::Arc;
+
+fn tree_span(formatter: &DefaultFormatter, span: &SpanRef, last: &[bool]) -> String {
+    let mut out = String::new();
+    formatter.format_tree_span(&mut Writer::Fmt(&mut out), span, last).unwrap();
+    out
+}
+
+#[test]
+fn rejects_invalid_timestamp_formats() {
+    assert!(Theme::new().timestamp(Timestamp::Utc("%H:%M".to_string())).is_ok());
+    assert!(Theme::new().timestamp(Timestamp::Local("%Q".to_string())).is_err());
+    assert!(Theme::new().timestamp(Timestamp::Utc("%".to_string())).is_err());
+}
+
+#[test]
+fn draws_glyphs_of_the_rows_drawn() {
+    let theme = Theme::new().tree(TreeGlyphs::lines());
+    let formatter = DefaultFormatter::with_color(false).theme(theme);
+
+    let root = Arc::new(Node::new(Span::default()));
+    let span = root.add(Node::new(Span::default().with_name("fetch".to_string())));
+
+    assert!(tree_span(&formatter, &span, &[]).ends_with(" fetch [0.0s]\n"));
+    assert!(tree_span(&formatter, &span, &[false, true]).starts_with("│  └─ "));
+    assert!(tree_span(&formatter, &span, &[true, false]).starts_with("   ├─ "));
+
+    assert_eq!(formatter.tree_indent(&[false, true], true), "│  └─ ");
+    assert_eq!(formatter.tree_indent(&[false, true], false), "│     ");
+}
+
+#[test]
+fn indents_with_spaces_by_default() {
+    let formatter = DefaultFormatter::with_color(false);
+    assert_eq!(formatter.tree_indent(&[false, true], true), "    ");
+}
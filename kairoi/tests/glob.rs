@@ -0,0 +1,12 @@
+// a glob import must not shadow anything from the prelude
+use kairoi::*;
+
+#[test]
+fn builtin_test_attribute_still_works() {
+    let _ = Level::Info;
+}
+
+#[test]
+fn prelude_println_is_not_shadowed() {
+    println!("still std::println");
+}
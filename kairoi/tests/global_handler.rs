@@ -0,0 +1,69 @@
+use kairoi::{Dispatcher, Event, GlobalHandler, Handler, SendSyncError, SpanRef};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// shuts down only once told to
+struct Stuck(Mutex<Receiver<()>>);
+
+impl Handler for Stuck {
+    fn handle(&self, _event: &Event) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        let _ = self.0.lock().unwrap().recv();
+        Ok(())
+    }
+}
+
+fn build(dispatcher: &Dispatcher) -> Result<GlobalHandler, String> {
+    GlobalHandler::builder()
+        .dispatcher(dispatcher.clone())
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn dispatchers_are_driven_by_one_handler_at_a_time() {
+    let dispatcher = Dispatcher::new();
+
+    let first = build(&dispatcher).unwrap();
+    assert!(build(&dispatcher).err().unwrap().contains("already driven"));
+
+    first.shutdown(Duration::from_secs(5)).unwrap();
+    build(&dispatcher).unwrap();
+}
+
+#[test]
+fn timed_out_shutdowns_keep_the_dispatcher_claimed() {
+    let dispatcher = Dispatcher::new();
+    let (unstick, stuck) = channel();
+    let global = GlobalHandler::builder()
+        .dispatcher(dispatcher.clone())
+        .handler(Box::new(Stuck(Mutex::new(stuck))))
+        .build()
+        .unwrap();
+
+    let e = global.shutdown(Duration::from_millis(50)).unwrap_err();
+    assert!(e.to_string().contains("stays claimed"), "{e}");
+    assert!(build(&dispatcher).err().unwrap().contains("already driven"));
+
+    unstick.send(()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match build(&dispatcher) {
+            Ok(_) => break,
+            Err(e) if Instant::now() < deadline => {
+                assert!(e.contains("already driven"), "{e}");
+                sleep(Duration::from_millis(10));
+            }
+            Err(e) => panic!("dispatcher was never released: {e}"),
+        }
+    }
+}
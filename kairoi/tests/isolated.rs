@@ -0,0 +1,24 @@
+mod common;
+
+use common::Recorder;
+use kairoi::{error, info, warn, AddIsolatedHandler, Dispatcher, GlobalHandler, Level, Overflow};
+
+#[test]
+fn isolated_handlers_get_only_events_of_interest() {
+    let dispatcher = Dispatcher::new();
+    let recorder = Recorder::at(Level::Warn);
+    let global = GlobalHandler::builder()
+        .dispatcher(dispatcher.clone())
+        .isolated_handler(Box::new(recorder.clone()), 16, Overflow::Block)
+        .build()
+        .unwrap();
+
+    dispatcher.with(|| {
+        info!("connected");
+        warn!("slow response");
+        error!("timed out");
+    });
+    global.control().flush().unwrap();
+
+    assert_eq!(recorder.messages(), ["slow response", "timed out"]);
+}
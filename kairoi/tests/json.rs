@@ -0,0 +1,93 @@
+mod common;
+
+use common::log;
+use kairoi::testing::capture;
+use kairoi::{info, Formatter, JsonFormatter, Node, Progress, Scope, Span, SpanRef, Writer};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn parse<T>(value: &T) -> Value
+where
+    JsonFormatter: Formatter<T>,
+{
+    let mut out = String::new();
+    JsonFormatter::new().format(&mut Writer::Fmt(&mut out), value).unwrap();
+
+    assert!(out.ends_with('\n'));
+    assert_eq!(out.lines().count(), 1, "{out}");
+    serde_json::from_str(&out).unwrap()
+}
+
+#[test]
+fn escapes_strings() {
+    let message = "say \"hi\" \\ back\nthen\ttab\r\u{1}\u{1f} ünïcödé 日本 🚀";
+    let value = parse(&*log(message, &[("quote\"key", "a\\b"), ("ctl", "\u{7}\u{0}")]));
+
+    assert_eq!(value["message"], message);
+    assert_eq!(value["fields"], json!({ "quote\"key": "a\\b", "ctl": "\u{7}\u{0}" }));
+}
+
+#[test]
+fn writes_logs_outside_of_spans() {
+    let value = parse(&*log("plain", &[]));
+
+    assert_eq!(value["kind"], "log");
+    assert_eq!(value["level"], "info");
+    assert_eq!(value["fields"], json!({}));
+    assert_eq!(value["span_id"], Value::Null);
+    assert_eq!(value["parent_id"], Value::Null);
+    assert_eq!(value["span_path"], json!([]));
+    assert_eq!(value["progress"], Value::Null);
+
+    let timestamp = value["timestamp"].as_str().unwrap();
+    assert!(timestamp.contains('T') && timestamp.ends_with('Z'), "{timestamp}");
+}
+
+#[tokio::test]
+async fn writes_logs_within_spans() {
+    let capture = capture();
+
+    Span::scope(async |s: Scope| {
+        s.update(Span::default().with_name("fetch".to_string()));
+        Span::scope(async |s: Scope| {
+            s.update(Span::default().with_name("parse".to_string()).with_progress(Progress::new(10, 3)));
+            info!("parsing");
+        })
+        .await;
+    })
+    .await;
+
+    let logs = capture.logs();
+    let value = parse(&*logs[0]);
+    let span = logs[0].span();
+
+    assert_eq!(value["span_id"], span.id().get());
+    assert_eq!(value["parent_id"], span.parent().unwrap().id().get());
+    assert_eq!(value["span_path"], json!(["fetch", "parse"]));
+    assert_eq!(value["progress"], json!({ "total": 10, "progress": 3 }));
+}
+
+#[test]
+fn writes_spans() {
+    let root: SpanRef = Arc::new(Node::new(Span::default()));
+    let fetch = root.add(Node::new(Span::default().with_name("fetch".to_string())));
+    let parse_span = fetch.add(Node::new(
+        Span::default().with_name("pa\"rse".to_string()).with_progress(Progress::new(4, 1)),
+    ));
+
+    let value = parse(&fetch);
+    assert_eq!(value["kind"], "span");
+    assert_eq!(value["name"], "fetch");
+    assert_eq!(value["status"], "running");
+    assert!(value["elapsed"].is_f64() || value["elapsed"].is_u64());
+    assert_eq!(value["span_id"], fetch.id().get());
+    assert_eq!(value["parent_id"], Value::Null);
+    assert_eq!(value["span_path"], json!(["fetch"]));
+    assert_eq!(value["progress"], Value::Null);
+
+    let value = parse(&parse_span);
+    assert_eq!(value["name"], "pa\"rse");
+    assert_eq!(value["parent_id"], fetch.id().get());
+    assert_eq!(value["span_path"], json!(["fetch", "pa\"rse"]));
+    assert_eq!(value["progress"], json!({ "total": 4, "progress": 1 }));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::log;
+use kairoi::{Formatter, LogfmtFormatter, Node, Progress, Span, SpanRef, Writer};
+use std::sync::Arc;
+
+// the line without `ts`
+fn format<T>(value: &T) -> String
+where
+    LogfmtFormatter: Formatter<T>,
+{
+    let mut out = String::new();
+    LogfmtFormatter::new().format(&mut Writer::Fmt(&mut out), value).unwrap();
+
+    assert_eq!(out.lines().count(), 1, "{out}");
+    let (ts, rest) = out.trim_end_matches('\n').split_once(' ').unwrap();
+    assert!(ts.starts_with("ts=") && ts.ends_with('Z'), "{ts}");
+    rest.to_string()
+}
+
+#[test]
+fn quotes_values_only_when_needed() {
+    let line = format(&*log("done", &[("rows", "42"), ("empty", ""), ("eq", "a=b"), ("path", "C:\\tmp")]));
+    assert_eq!(line, r#"level=info msg=done rows=42 empty="" eq="a=b" path="C:\\tmp""#);
+}
+
+#[test]
+fn escapes_quotes_and_control_characters() {
+    let line = format(&*log("say \"hi\"\nthen\ttab\u{1}", &[("name", "日本")]));
+    assert_eq!(line, r#"level=info msg="say \"hi\"\nthen\ttab\u0001" name=日本"#);
+}
+
+#[test]
+fn sanitizes_keys() {
+    let line = format(&*log("m", &[("a key", "1"), ("k=v", "2"), ("q\"", "3"), ("", "4"), ("new\nline", "5")]));
+    assert_eq!(line, "level=info msg=m a_key=1 k_v=2 q_=3 _=4 new_line=5");
+}
+
+#[test]
+fn prefixes_fields_named_like_reserved_keys() {
+    let line = format(&*log("m", &[("ts", "1"), ("level", "2"), ("msg", "3"), ("span", "4"), ("span id", "5")]));
+    assert_eq!(
+        line,
+        "level=info msg=m field.ts=1 field.level=2 field.msg=3 field.span=4 field.span_id=5"
+    );
+}
+
+#[test]
+fn writes_span_ids() {
+    let root: SpanRef = Arc::new(Node::new(Span::default()));
+    let fetch = root.add(Node::new(Span::default().with_name("fetch".to_string())));
+    let parse = fetch.add(Node::new(
+        Span::default().with_name("parse rows".to_string()).with_progress(Progress::new(10, 3)),
+    ));
+
+    let line = format(&fetch);
+    assert!(
+        line.starts_with(&format!("span=fetch span_id={} status=running elapsed=", fetch.id().get())),
+        "{line}"
+    );
+
+    let line = format(&parse);
+    let prefix = format!(
+        "span=\"fetch/parse rows\" span_id={} parent_id={} status=running elapsed=",
+        parse.id().get(),
+        fetch.id().get()
+    );
+    assert!(line.starts_with(&prefix), "{line}");
+    assert!(line.ends_with(" progress=3/10"), "{line}");
+}
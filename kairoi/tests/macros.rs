@@ -0,0 +1,35 @@
+use kairoi::testing::capture;
+use kairoi::{info, log, warn, Level};
+
+#[test]
+fn positional_and_field_forms_compile_side_by_side() {
+    let capture = capture();
+    let rows = 3;
+    let name = "ann";
+
+    log!(Level::Info, "plain");
+    log!(Level::Info, "{} rows", rows);
+    log!(Level::Info, "{} rows for {}", rows, name);
+    info!("{rows} rows for {name}");
+    warn!("{}", rows);
+    log!(Level::Info, user = name, attempt = 2; "{} rows", rows);
+    info!(user = name; "fetched");
+
+    let logs = capture.logs();
+    let messages: Vec<&str> = logs.iter().map(|log| log.message()).collect();
+    assert_eq!(
+        messages,
+        ["plain", "3 rows", "3 rows for ann", "3 rows for ann", "3", "3 rows", "fetched"]
+    );
+
+    assert!(logs[..5].iter().all(|log| log.fields().is_empty()));
+    let fields = |i: usize| -> Vec<(String, String)> { logs[i].fields().to_vec() };
+    assert_eq!(
+        fields(5),
+        [
+            ("user".to_string(), "ann".to_string()),
+            ("attempt".to_string(), "2".to_string())
+        ]
+    );
+    assert_eq!(fields(6), [("user".to_string(), "ann".to_string())]);
+}
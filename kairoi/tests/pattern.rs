@@ -0,0 +1,86 @@
+mod common;
+
+use common::log;
+use kairoi::{Formatter, Log, Node, PatternFormatter, Span, SpanRef, Writer};
+use std::sync::Arc;
+
+fn format_log(pattern: &str, log: &Log) -> String {
+    let mut out = String::new();
+    let formatter = PatternFormatter::new(pattern).unwrap();
+    Formatter::<Log>::format(&formatter, &mut Writer::Fmt(&mut out), log).unwrap();
+    out
+}
+
+fn format_span(pattern: &str, span: &SpanRef) -> String {
+    let mut out = String::new();
+    let formatter = PatternFormatter::with_span("{message}", pattern).unwrap();
+    Formatter::<SpanRef>::format(&formatter, &mut Writer::Fmt(&mut out), span).unwrap();
+    out
+}
+
+#[test]
+fn fills_in_placeholders() {
+    let log = log("fetched", &[("rows", "42"), ("user", "ann")]);
+    assert_eq!(format_log("{level} {message} {fields}", &log), "INFO fetched rows=42 user=ann\n");
+
+    // logs outside of any span have no span name, and the span placeholders of logs are empty
+    assert_eq!(format_log("[{span}] {message}{name}{status}", &log), "[] fetched\n");
+}
+
+#[test]
+fn escapes_braces() {
+    let log = log("fetched", &[]);
+    assert_eq!(format_log("{{{message}}} {{}}", &log), "{fetched} {}\n");
+}
+
+#[test]
+fn aligns_to_width() {
+    let log = log("ab", &[]);
+    assert_eq!(format_log("[{message:6}]", &log), "[ab    ]\n");
+    assert_eq!(format_log("[{message:<6}]", &log), "[ab    ]\n");
+    assert_eq!(format_log("[{message:>6}]", &log), "[    ab]\n");
+    assert_eq!(format_log("[{message:^7}]", &log), "[  ab   ]\n");
+    assert_eq!(format_log("[{message:>1}]", &log), "[ab]\n");
+}
+
+#[test]
+fn aligns_wide_characters_by_columns() {
+    let log = log("日本", &[]);
+    assert_eq!(format_log("[{message:>6}]", &log), "[  日本]\n");
+    assert_eq!(format_log("[{message:^6}]", &log), "[ 日本 ]\n");
+}
+
+#[test]
+fn formats_time() {
+    let log = log("fetched", &[]);
+    let year = format_log("{time:%Y}", &log);
+    assert_eq!(year.trim_end().len(), 4);
+    assert!(year.trim_end().chars().all(|c| c.is_ascii_digit()));
+}
+
+#[test]
+fn formats_spans() {
+    let root = Arc::new(Node::new(Span::default()));
+    let fetch = root.add(Node::new(Span::default().with_name("fetch".to_string())));
+    let parse = fetch.add(Node::new(Span::default().with_name("parse".to_string())));
+
+    assert_eq!(format_span("{indent}{name} {status}", &fetch), "fetch running\n");
+    assert_eq!(format_span("{indent}{name} {status}", &parse), "  parse running\n");
+    assert_eq!(format_span("{span_path} {progress}|", &parse), "fetch/parse |\n");
+}
+
+#[test]
+fn rejects_invalid_patterns() {
+    for pattern in [
+        "{nope}",
+        "{level",
+        "level}",
+        "{level:>x}",
+        "{time:%Q}",
+        "{time:%}",
+    ] {
+        assert!(PatternFormatter::new(pattern).is_err(), "{pattern}");
+    }
+    assert!(PatternFormatter::with_span("{message}", "{name:^}").is_ok());
+    assert!(PatternFormatter::with_span("{message}", "{unknown}").is_err());
+}
@@ -0,0 +1,216 @@
+use kairoi::{
+    info, Dispatcher, Event, FailurePolicy, GlobalHandler, Handler, Recovery, SendSyncError, SpanRef,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+// fails every event it gets
+#[derive(Clone, Default)]
+struct Failing {
+    handled: Arc<AtomicUsize>,
+    shutdowns: Arc<AtomicUsize>,
+    fails_to_start: bool,
+    fails_to_shut_down: bool,
+}
+
+impl Handler for Failing {
+    fn handle(&self, _event: &Event) -> Result<(), SendSyncError> {
+        self.handled.fetch_add(1, Ordering::SeqCst);
+        Err("handle failed".into())
+    }
+
+    fn tick(&self, _root: &SpanRef) -> Result<(), SendSyncError> {
+        Ok(())
+    }
+
+    fn on_start(&self) -> Result<(), SendSyncError> {
+        if self.fails_to_start {
+            return Err("start failed".into());
+        }
+        Ok(())
+    }
+
+    fn shutdown(&self) -> Result<(), SendSyncError> {
+        self.shutdowns.fetch_add(1, Ordering::SeqCst);
+        if self.fails_to_shut_down {
+            return Err("shutdown failed".into());
+        }
+        Ok(())
+    }
+
+    fn wants_tick(&self) -> bool {
+        false
+    }
+}
+
+struct Harness {
+    dispatcher: Dispatcher,
+    global: GlobalHandler,
+    failures: Receiver<(Recovery, String)>,
+}
+
+impl Harness {
+    fn new(handler: Failing, policy: FailurePolicy) -> Self {
+        let dispatcher = Dispatcher::new();
+        let (tx, failures) = channel();
+        let global = GlobalHandler::builder()
+            .dispatcher(dispatcher.clone())
+            .handler_with_policy(Box::new(handler), policy)
+            .on_error(move |failure| {
+                let _ = tx.send((failure.recovery(), failure.error().to_string()));
+            })
+            .build()
+            .unwrap();
+
+        Self {
+            dispatcher,
+            global,
+            failures,
+        }
+    }
+
+    fn log(&self) {
+        self.dispatcher.with(|| info!("event"));
+    }
+
+    fn failure(&self) -> (Recovery, String) {
+        self.failures.recv_timeout(Duration::from_secs(5)).expect("handler failed")
+    }
+
+    // fails the handler once, waiting out its backoff
+    fn fail(&self) -> Recovery {
+        self.log();
+        let (recovery, _) = self.failure();
+        if let Recovery::Retrying { after, .. } | Recovery::Restarted { after, .. } = recovery {
+            sleep(after + Duration::from_millis(5));
+        }
+        recovery
+    }
+
+    fn attached(&self) -> bool {
+        let ids = self.global.control().handlers().unwrap();
+        ids.contains(&self.global.handler_ids()[0])
+    }
+}
+
+#[test]
+fn retries_with_doubling_backoff_until_removed() {
+    let handler = Failing::default();
+    let backoff = Duration::from_millis(10);
+    let harness = Harness::new(handler.clone(), FailurePolicy::Retry { max_retries: 3, backoff });
+
+    let recoveries: Vec<Recovery> = (0..4).map(|_| harness.fail()).collect();
+    assert_eq!(
+        recoveries,
+        [
+            Recovery::Retrying { attempt: 1, after: backoff },
+            Recovery::Retrying { attempt: 2, after: backoff * 2 },
+            Recovery::Retrying { attempt: 3, after: backoff * 4 },
+            Recovery::Removed,
+        ]
+    );
+    assert!(!harness.attached());
+
+    harness.log();
+    harness.global.flush().unwrap();
+    assert_eq!(handler.handled.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn backoff_stops_doubling_after_sixteen_failures() {
+    let backoff = Duration::from_nanos(1);
+    let harness = Harness::new(Failing::default(), FailurePolicy::Retry { max_retries: 20, backoff });
+
+    for attempt in 1..=20 {
+        let after = backoff * (1 << (attempt - 1).min(16));
+        assert_eq!(harness.fail(), Recovery::Retrying { attempt, after });
+    }
+    assert_eq!(harness.fail(), Recovery::Removed);
+}
+
+#[test]
+fn events_are_skipped_while_backing_off() {
+    let handler = Failing::default();
+    let policy = FailurePolicy::Retry {
+        max_retries: 3,
+        backoff: Duration::from_secs(3600),
+    };
+    let harness = Harness::new(handler.clone(), policy);
+
+    harness.log();
+    harness.failure();
+    harness.log();
+    harness.log();
+    harness.global.flush().unwrap();
+
+    assert_eq!(handler.handled.load(Ordering::SeqCst), 1);
+    assert!(harness.attached());
+}
+
+#[test]
+fn restarts_until_removed() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let handler = Failing::default();
+    let factory = {
+        let (created, handler) = (created.clone(), handler.clone());
+        Arc::new(move || -> Box<dyn Handler> {
+            created.fetch_add(1, Ordering::SeqCst);
+            Box::new(handler.clone())
+        })
+    };
+    let policy = FailurePolicy::Restart {
+        factory,
+        max_restarts: 2,
+        backoff: Duration::ZERO,
+    };
+    let harness = Harness::new(handler.clone(), policy);
+
+    let recoveries: Vec<Recovery> = (0..3).map(|_| harness.fail()).collect();
+    let after = Duration::ZERO;
+    assert_eq!(
+        recoveries,
+        [
+            Recovery::Restarted { attempt: 1, after },
+            Recovery::Restarted { attempt: 2, after },
+            Recovery::Removed,
+        ]
+    );
+    assert!(!harness.attached());
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+    // the replaced ones
+    assert_eq!(handler.shutdowns.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn failures_while_restarting_are_reported() {
+    let handler = Failing {
+        fails_to_shut_down: true,
+        ..Failing::default()
+    };
+    let factory = Arc::new(|| -> Box<dyn Handler> {
+        Box::new(Failing {
+            fails_to_start: true,
+            ..Failing::default()
+        })
+    });
+    let policy = FailurePolicy::Restart {
+        factory,
+        max_restarts: 3,
+        backoff: Duration::ZERO,
+    };
+    let harness = Harness::new(handler, policy);
+
+    harness.log();
+    assert_eq!(
+        [harness.failure(), harness.failure(), harness.failure()],
+        [
+            (Recovery::Removed, "handle failed".to_string()),
+            (Recovery::Removed, "shutdown failed".to_string()),
+            (Recovery::Removed, "start failed".to_string()),
+        ]
+    );
+    assert!(!harness.attached());
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "serde")]
+
+use kairoi::testing::capture;
+use kairoi::{
+    info, Event, Formatter, JsonFormatter, Level, Log, Node, Progress, Scope, Span, SpanRef,
+    SpanSnapshot, Status, Writer,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn assert_same_span(a: &Span, b: &Span) {
+    assert_eq!(a.timestamp(), b.timestamp());
+    assert_eq!(a.name(), b.name());
+    assert_eq!(a.status(), b.status());
+    assert_eq!(a.ended(), b.ended());
+    assert_eq!(a.progress().map(|p| (p.total(), p.progress())), b.progress().map(|p| (p.total(), p.progress())));
+}
+
+fn json_formatted(log: &Log) -> Value {
+    let mut out = String::new();
+    JsonFormatter::new().format(&mut Writer::Fmt(&mut out), log).unwrap();
+    serde_json::from_str(&out).unwrap()
+}
+
+#[tokio::test]
+async fn logs_round_trip() {
+    let capture = capture();
+
+    Span::scope(async |s: Scope| {
+        s.update(Span::default().with_name("fetch".to_string()));
+        Span::scope(async |s: Scope| {
+            s.update(Span::default().with_name("parse".to_string()).with_progress(Progress::new(10, 3)));
+            info!(rows = 42, at = "日本"; "parsed \"rows\"");
+        })
+        .await;
+    })
+    .await;
+
+    let log = capture.logs()[0].clone();
+    let written = serde_json::to_string(&*log).unwrap();
+    let read: Log = serde_json::from_str(&written).unwrap();
+
+    assert_eq!(read.timestamp(), log.timestamp());
+    assert_eq!(read.level(), Level::Info);
+    assert_eq!(read.message(), log.message());
+    assert_eq!(read.fields(), log.fields());
+    assert_eq!(read.span().path(), ["fetch", "parse"]);
+    assert_eq!(read.span().id(), log.span().id());
+    assert_eq!(read.span().parent().unwrap().id(), log.span().parent().unwrap().id());
+    assert_same_span(&read.span().value(), &log.span().value());
+}
+
+#[test]
+fn logs_outside_of_spans_round_trip() {
+    let log = match Event::log(Level::Warn, "plain".to_string()) {
+        Event::Log(log) => log,
+        _ => unreachable!(),
+    };
+
+    let read: Log = serde_json::from_str(&serde_json::to_string(&*log).unwrap()).unwrap();
+    assert_eq!(read.span().depth(), 0);
+    assert!(read.span().path().is_empty());
+    assert_eq!(read.message(), "plain");
+}
+
+#[tokio::test]
+async fn logs_have_the_keys_of_json_formatter() {
+    let capture = capture();
+
+    Span::scope(async |s: Scope| {
+        s.update(Span::default().with_name("fetch".to_string()).with_progress(Progress::new(4, 1)));
+        info!(url = "https://example.com"; "fetching");
+    })
+    .await;
+    info!("done");
+
+    for log in capture.logs() {
+        let mut written = serde_json::to_value(&*log).unwrap();
+        let spans = written.as_object_mut().unwrap().remove("spans").unwrap();
+        assert_eq!(spans.as_array().unwrap().len(), log.span().path().len());
+
+        assert_eq!(written, json_formatted(&log));
+    }
+}
+
+#[test]
+fn spans_round_trip() {
+    let span = Span::default()
+        .with_name("fetch".to_string())
+        .with_progress(Progress::new(10, 3))
+        .with_status(Status::Failed);
+
+    let written = serde_json::to_value(&span).unwrap();
+    assert!(written["timestamp"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(written["status"], "failed");
+    assert_eq!(written["progress"], json!({ "total": 10, "progress": 3 }));
+    assert_eq!(written["ended"], Value::Null);
+
+    let read: Span = serde_json::from_value(written.clone()).unwrap();
+    assert_same_span(&read, &span);
+
+    let mut running = written;
+    running.as_object_mut().unwrap().remove("ended");
+    let read: Span = serde_json::from_value(running).unwrap();
+    assert_eq!(read.ended(), None);
+    assert_same_span(&read, &span);
+}
+
+#[tokio::test]
+async fn ended_spans_round_trip() {
+    let capture = capture();
+    Span::scope(async |s: Scope| s.update(Span::default().with_name("fetch".to_string()))).await;
+
+    let span = capture.spans()[0].span().clone();
+    assert!(span.ended().is_some());
+
+    let read: Span = serde_json::from_str(&serde_json::to_string(&span).unwrap()).unwrap();
+    assert_same_span(&read, &span);
+}
+
+#[test]
+fn snapshots_round_trip() {
+    let root: SpanRef = Arc::new(Node::new(Span::default().with_name("serve".to_string())));
+    let fetch = root.add(Node::new(Span::default().with_name("fetch".to_string())));
+    root.add(Node::new(Span::default().with_name("parse".to_string())));
+    fetch.add(Node::new(Span::default().with_name("retry".to_string())));
+
+    let snapshot = SpanSnapshot::capture(&root);
+    let written = serde_json::to_value(&snapshot).unwrap();
+    assert_eq!(written["span_id"], root.id().get());
+    assert_eq!(written["name"], "serve");
+    assert_eq!(written["children"][0]["children"][0]["name"], "retry");
+
+    let read: SpanSnapshot = serde_json::from_value(written).unwrap();
+    fn assert_same(a: &SpanSnapshot, b: &SpanSnapshot) {
+        assert_eq!(a.id(), b.id());
+        assert_same_span(a.span(), b.span());
+        assert_eq!(a.children().len(), b.children().len());
+        for (a, b) in a.children().iter().zip(b.children()) {
+            assert_same(a, b);
+        }
+    }
+    assert_same(&read, &snapshot);
+    assert_eq!(read.to_string(), snapshot.to_string());
+}
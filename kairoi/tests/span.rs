@@ -0,0 +1,39 @@
+use kairoi::testing::capture;
+use kairoi::{Scope, Span, Status};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+fn status(capture: &kairoi::testing::Capture, name: &str) -> Status {
+    let spans = capture.spans();
+    let span = spans.iter().find(|span| span.matches(&[name])).expect("span was captured");
+    span.span().status()
+}
+
+#[tokio::test]
+async fn dropped_spans_are_cancelled() {
+    let capture = capture();
+
+    let slow = Span::scope(async |s: Scope| {
+        s.update(Span::default().with_name("slow".to_string()));
+        sleep(Duration::from_secs(10)).await;
+    });
+    assert!(timeout(Duration::from_millis(10), slow).await.is_err());
+
+    Span::scope(async |s: Scope| s.update(Span::default().with_name("fast".to_string()))).await;
+
+    assert_eq!(status(&capture, "slow"), Status::Cancelled);
+    assert_eq!(status(&capture, "fast"), Status::Completed);
+}
+
+#[tokio::test]
+async fn failed_spans_stay_failed() {
+    let capture = capture();
+
+    Span::scope(async |s: Scope| {
+        s.fail();
+        s.update(Span::default().with_name("fetch".to_string()));
+    })
+    .await;
+
+    assert_eq!(status(&capture, "fetch"), Status::Failed);
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "tui")]
+
+use kairoi::{Event, Handler, Key, Level, Node, Span, SpanRef, TuiHandler, VirtualBackend};
+use std::sync::Arc;
+
+fn setup() -> (TuiHandler, VirtualBackend, SpanRef) {
+    let backend = VirtualBackend::new(120, 20);
+    let handler = TuiHandler::with_backend(Box::new(backend.clone()));
+    let root = Arc::new(Node::new(Span::default()));
+    (handler, backend, root)
+}
+
+fn log(handler: &TuiHandler, message: &str) {
+    handler.handle(&Event::log(Level::Info, message.to_string())).unwrap();
+}
+
+fn span(root: &SpanRef, name: &str) -> SpanRef {
+    root.add(Node::new(Span::default().with_name(name.to_string())))
+}
+
+fn shows(backend: &VirtualBackend, text: &str) -> bool {
+    backend.text().iter().any(|line| line.contains(text))
+}
+
+// lines of the spans and detail panes
+fn right(backend: &VirtualBackend) -> Vec<String> {
+    backend
+        .text()
+        .iter()
+        .skip(1)
+        .filter_map(|line| line.split_once('│').map(|(_, right)| right.trim_end().to_string()))
+        .collect()
+}
+
+#[test]
+fn draws_logs_and_spans() {
+    let (handler, backend, root) = setup();
+    log(&handler, "hello");
+    let _fetch = span(&root, "fetch");
+
+    handler.tick(&root).unwrap();
+
+    assert!(backend.active());
+    assert_eq!(backend.text().len(), 20);
+    assert!(backend.text()[0].contains("1 spans │ 1 logs"));
+    assert!(shows(&backend, "hello"));
+    assert!(right(&backend).iter().any(|line| line.contains("fetch")));
+}
+
+#[test]
+fn filters_logs() {
+    let (handler, backend, root) = setup();
+    log(&handler, "apple");
+    log(&handler, "banana");
+
+    for key in [Key::Char('/'), Key::Char('b'), Key::Char('a'), Key::Enter] {
+        backend.press(key);
+    }
+    handler.tick(&root).unwrap();
+
+    assert!(backend.text()[0].contains("filter: ba"));
+    assert!(shows(&backend, "banana"));
+    assert!(!shows(&backend, "apple"));
+
+    backend.press(Key::Esc);
+    handler.tick(&root).unwrap();
+    assert!(shows(&backend, "apple"));
+}
+
+#[test]
+fn scrolls_logs() {
+    let (handler, backend, root) = setup();
+    for i in 0..50 {
+        log(&handler, &format!("log #{i};"));
+    }
+
+    handler.tick(&root).unwrap();
+    assert!(shows(&backend, "log #49;"));
+    assert!(!shows(&backend, "log #0;"));
+
+    backend.press(Key::Home);
+    handler.tick(&root).unwrap();
+    assert!(shows(&backend, "log #0;"));
+    assert!(!shows(&backend, "log #49;"));
+
+    // new logs don't move the view while scrolled up
+    log(&handler, "log #50;");
+    handler.tick(&root).unwrap();
+    assert!(shows(&backend, "log #0;"));
+
+    backend.press(Key::End);
+    handler.tick(&root).unwrap();
+    assert!(shows(&backend, "log #50;"));
+}
+
+#[test]
+fn selects_spans() {
+    let (handler, backend, root) = setup();
+    let _alpha = span(&root, "alpha");
+    let _beta = span(&root, "beta");
+
+    handler.tick(&root).unwrap();
+    let detail = |backend: &VirtualBackend| {
+        let right = right(backend);
+        let title = right.iter().position(|line| line.trim() == "Detail").unwrap();
+        right[title + 1].clone()
+    };
+    assert_eq!(detail(&backend), "alpha");
+
+    // arrows scroll logs until the span pane has focus
+    backend.press(Key::Down);
+    handler.tick(&root).unwrap();
+    assert_eq!(detail(&backend), "alpha");
+
+    backend.press(Key::Tab);
+    backend.press(Key::Down);
+    handler.tick(&root).unwrap();
+    assert_eq!(detail(&backend), "beta");
+}
+
+#[test]
+fn gives_the_screen_back() {
+    let (handler, backend, root) = setup();
+    log(&handler, "hello");
+    handler.tick(&root).unwrap();
+
+    handler.suspend().unwrap();
+    assert!(!backend.active());
+    handler.tick(&root).unwrap();
+    assert!(backend.active());
+
+    handler.shutdown().unwrap();
+    assert!(!backend.active());
+    assert!(backend.printed().contains("hello"));
+}
+
+#[test]
+fn tells_when_too_small() {
+    let (handler, backend, root) = setup();
+    backend.resize(20, 5);
+
+    handler.tick(&root).unwrap();
+    assert_eq!(backend.text()[0].trim_end(), "terminal too small");
+}